@@ -0,0 +1,40 @@
+// 统计内存分配次数的全局分配器，只在测试中使用
+// 计数按线程分别进行，因此并行运行的其他测试不会影响结果
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct Counter;
+
+thread_local! {
+    static COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+fn increase() {
+    let _ = COUNT.try_with(|x| x.set(x.get() + 1));
+}
+
+unsafe impl GlobalAlloc for Counter {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        increase();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        increase();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counter = Counter;
+
+// 执行 f，返回其结果以及执行过程中（当前线程上）发生的内存分配次数
+pub fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = COUNT.with(|x| x.get());
+    let result = f();
+    (result, COUNT.with(|x| x.get()) - before)
+}
@@ -0,0 +1,84 @@
+use crate::grammar::{FunctionCall, FunctionDecl, Statement};
+use crate::prog::{Prog, ENTRY_POINT};
+use crate::visitor::{walk_function_call, Visitor};
+use std::collections::HashSet;
+
+/**
+ * 找出声明了但从未被调用的函数（顶层的入口函数 main 除外）
+ * prog 需要已经完成引用消解，返回的函数名按声明顺序排列，嵌套声明的函数排在其外层函数之后
+ */
+pub fn unused_functions(prog: &Prog) -> Vec<String> {
+    unused_function_decls(prog)
+        .iter()
+        .map(|x| x.name.to_string())
+        .collect()
+}
+
+// 与 unused_functions 相同，但返回函数声明本身
+pub fn unused_function_decls(prog: &Prog) -> Vec<&FunctionDecl> {
+    let mut collector = CalledFunctions::default();
+    collector.visit_prog(prog);
+
+    let mut unused = Vec::new();
+    collect_unused(&prog.stmts, &[], &collector.called, &mut unused);
+    unused
+}
+
+// 收集 stmts 中（包括嵌套的函数体中）未被调用的函数，path 为 stmts 所在函数的声明路径
+fn collect_unused<'a>(
+    stmts: &'a [Statement],
+    path: &[usize],
+    called: &HashSet<Vec<usize>>,
+    unused: &mut Vec<&'a FunctionDecl>,
+) {
+    for (i, x) in stmts.iter().enumerate() {
+        if let Statement::FunctionDecl(decl) = x {
+            let path = [path, &[i]].concat();
+            let is_entry_point = path.len() == 1 && &*decl.name == ENTRY_POINT;
+            if !is_entry_point && !called.contains(&path) {
+                unused.push(decl);
+            }
+            collect_unused(&decl.body.stmts, &path, called, unused);
+        }
+    }
+}
+
+// 收集所有被调用到的函数声明（即 FunctionCall::definition）
+#[derive(Default)]
+struct CalledFunctions {
+    called: HashSet<Vec<usize>>,
+}
+impl<'ast> Visitor<'ast> for CalledFunctions {
+    fn visit_function_call(&mut self, call: &'ast FunctionCall) {
+        if let Some(path) = &call.definition {
+            self.called.insert(path.clone());
+        }
+        walk_function_call(self, call);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_str;
+    use crate::ref_resolver::RefResolver;
+
+    fn unused(code: &str) -> Vec<String> {
+        let mut prog = parse_str(code).unwrap();
+        RefResolver::resolve(&mut prog).unwrap();
+        unused_functions(&prog)
+    }
+
+    #[test]
+    fn reports_only_uncalled_functions() {
+        assert_eq!(
+            unused("function called(){} function uncalled(){} called();"),
+            vec!["uncalled"]
+        );
+        // 入口函数 main 不算未使用，嵌套声明的函数同样会被检查
+        assert_eq!(
+            unused("function main(){ function inner(){} }"),
+            vec!["inner"]
+        );
+    }
+}
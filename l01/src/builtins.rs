@@ -0,0 +1,323 @@
+use crate::value::Value;
+use std::io::Write;
+
+// 内置函数可以使用的宿主环境
+pub struct Host<'a> {
+    pub out: &'a mut dyn Write, // 程序的输出
+    pub err: &'a mut dyn Write, // 程序的错误输出
+    pub clock: &'a Clock,       // 时钟，供 time 使用
+}
+
+// 时钟，返回从某个固定时刻起经过的毫秒数
+pub type Clock = dyn Fn() -> i64;
+
+// 内置函数
+type Builtin = fn(&mut Host, &[Value]) -> Result<Value, String>;
+
+// 内置函数表，引用消解与解释执行都以此为准
+const BUILTINS: &[(&str, Builtin)] = &[
+    ("println", println),
+    ("print", print),
+    ("eprintln", eprintln),
+    ("assert", assert),
+    ("len", len),
+    ("str", str),
+    ("int", int),
+    ("typeof", type_of),
+    ("concat", concat),
+    ("time", time),
+    ("abs", abs),
+    ("min", min),
+    ("max", max),
+];
+
+// 查找内置函数
+pub fn get(name: &str) -> Option<Builtin> {
+    BUILTINS.iter().find(|(x, _)| *x == name).map(|(_, f)| *f)
+}
+
+pub fn is_builtin(name: &str) -> bool {
+    get(name).is_some()
+}
+
+// 生成 println / print / eprintln 输出的文本
+// 第一个参数是包含 {} 占位符的字符串时，依次用其余参数替换各个占位符，占位符与参数的个数必须相同；
+// 否则以空格连接所有参数
+fn format(args: &[Value]) -> Result<String, String> {
+    let (template, rest) = match args.split_first() {
+        Some((Value::Str(x), rest)) if x.contains("{}") => (x, rest),
+        _ => {
+            return Ok(args
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+                .join(" "))
+        }
+    };
+
+    let placeholders = template.matches("{}").count();
+    if placeholders != rest.len() {
+        return Err(format!(
+            "format string has {} placeholder(s), got {} argument(s)",
+            placeholders,
+            rest.len()
+        ));
+    }
+    let mut parts = template.split("{}");
+    let mut result = parts.next().unwrap_or_default().to_string();
+    for (x, part) in rest.iter().zip(parts) {
+        result += &x.to_string();
+        result += part;
+    }
+    Ok(result)
+}
+
+fn println(host: &mut Host, args: &[Value]) -> Result<Value, String> {
+    writeln!(host.out, "{}", format(args)?).map_err(|e| e.to_string())?;
+    Ok(Value::Null)
+}
+
+fn print(host: &mut Host, args: &[Value]) -> Result<Value, String> {
+    write!(host.out, "{}", format(args)?).map_err(|e| e.to_string())?;
+    Ok(Value::Null)
+}
+
+// eprintln(x...)：与 println 相同，但写入错误输出，使程序的诊断信息与正常输出区分开
+fn eprintln(host: &mut Host, args: &[Value]) -> Result<Value, String> {
+    writeln!(host.err, "{}", format(args)?).map_err(|e| e.to_string())?;
+    Ok(Value::Null)
+}
+
+// assert(cond)：cond 为 false 时报错
+fn assert(_: &mut Host, args: &[Value]) -> Result<Value, String> {
+    match args {
+        [Value::Bool(true)] => Ok(Value::Null),
+        [Value::Bool(false)] => Err("assertion failed".to_string()),
+        [x] => Err(format!("Type error: assert expects a bool, got {}", x)),
+        _ => Err(format!("assert expects 1 argument, got {}", args.len())),
+    }
+}
+
+// len(s)：返回字符串的字符个数（而不是字节数）
+fn len(_: &mut Host, args: &[Value]) -> Result<Value, String> {
+    match args {
+        [Value::Str(x)] => Ok(Value::Int(x.chars().count() as i64)),
+        [x] => Err(format!("Type error: len expects a string, got {}", x)),
+        _ => Err(format!("len expects 1 argument, got {}", args.len())),
+    }
+}
+
+// str(x)：把任意值转换为字符串，与 println 的输出一致
+fn str(_: &mut Host, args: &[Value]) -> Result<Value, String> {
+    match args {
+        [x] => Ok(Value::Str(x.to_string())),
+        _ => Err(format!("str expects 1 argument, got {}", args.len())),
+    }
+}
+
+// concat(s...)：不加分隔符地连接任意个字符串，没有参数时返回空字符串
+// 参数不会被自动转换，不是字符串时报错（需要时可以先用 str 转换）
+fn concat(_: &mut Host, args: &[Value]) -> Result<Value, String> {
+    let mut result = String::new();
+    for x in args {
+        match x {
+            Value::Str(x) => result.push_str(x),
+            x => return Err(format!("Type error: concat expects strings, got {}", x)),
+        }
+    }
+    Ok(Value::Str(result))
+}
+
+// typeof(x)：返回值的类型名称 "int"、"string"、"char"、"bool" 或 "null"
+fn type_of(_: &mut Host, args: &[Value]) -> Result<Value, String> {
+    match args {
+        [x] => Ok(Value::Str(x.type_name().to_string())),
+        _ => Err(format!("typeof expects 1 argument, got {}", args.len())),
+    }
+}
+
+// int(x)：把字符串解析为整数，整数原样返回
+fn int(_: &mut Host, args: &[Value]) -> Result<Value, String> {
+    match args {
+        [Value::Int(x)] => Ok(Value::Int(*x)),
+        [Value::Str(x)] => x
+            .parse()
+            .map(Value::Int)
+            .map_err(|_| format!("int: cannot parse {:?} as an integer", x)),
+        [x] => Err(format!(
+            "Type error: int expects a string or an int, got {}",
+            x
+        )),
+        _ => Err(format!("int expects 1 argument, got {}", args.len())),
+    }
+}
+
+// abs(x)：返回整数的绝对值
+fn abs(_: &mut Host, args: &[Value]) -> Result<Value, String> {
+    match args {
+        [Value::Int(x)] => x
+            .checked_abs()
+            .map(Value::Int)
+            .ok_or_else(|| format!("Integer overflow: abs({})", x)),
+        [x] => Err(format!("Type error: abs expects an int, got {}", x)),
+        _ => Err(format!("abs expects 1 argument, got {}", args.len())),
+    }
+}
+
+// min(x, y...)：返回两个或更多整数中最小的一个
+fn min(_: &mut Host, args: &[Value]) -> Result<Value, String> {
+    ints("min", args).map(|x| Value::Int(x.into_iter().min().unwrap_or_default()))
+}
+
+// max(x, y...)：返回两个或更多整数中最大的一个
+fn max(_: &mut Host, args: &[Value]) -> Result<Value, String> {
+    ints("max", args).map(|x| Value::Int(x.into_iter().max().unwrap_or_default()))
+}
+
+// 检查 min / max 的参数：至少两个，且都是整数
+fn ints(name: &str, args: &[Value]) -> Result<Vec<i64>, String> {
+    if args.len() < 2 {
+        return Err(format!(
+            "{} expects at least 2 arguments, got {}",
+            name,
+            args.len()
+        ));
+    }
+    args.iter()
+        .map(|x| match x {
+            Value::Int(x) => Ok(*x),
+            x => Err(format!("Type error: {} expects ints, got {}", name, x)),
+        })
+        .collect()
+}
+
+// time()：返回从解释器创建时起经过的毫秒数，用于给程序计时
+fn time(host: &mut Host, args: &[Value]) -> Result<Value, String> {
+    match args {
+        [] => Ok(Value::Int((host.clock)())),
+        _ => Err(format!("time expects 0 arguments, got {}", args.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 调用内置函数，输出被丢弃
+    fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+        let mut host = Host {
+            out: &mut Vec::new(),
+            err: &mut Vec::new(),
+            clock: &|| 0,
+        };
+        get(name).unwrap()(&mut host, args)
+    }
+
+    #[test]
+    fn str_converts_every_value() {
+        let cases = vec![
+            (Value::Int(42), "42"),
+            (Value::Int(-7), "-7"),
+            (Value::Bool(true), "true"),
+            (Value::Bool(false), "false"),
+            (Value::Null, "null"),
+            (Value::Char('c'), "c"),
+            (Value::Str("hi".to_string()), "hi"),
+        ];
+        for (x, expected) in cases {
+            assert_eq!(call("str", &[x]), Ok(Value::Str(expected.to_string())));
+        }
+    }
+
+    #[test]
+    fn int_parses_strings() {
+        assert_eq!(
+            call("int", &[Value::Str("42".to_string())]),
+            Ok(Value::Int(42))
+        );
+        assert_eq!(
+            call("int", &[Value::Str("x".to_string())]),
+            Err("int: cannot parse \"x\" as an integer".to_string())
+        );
+        assert_eq!(call("int", &[Value::Int(7)]), Ok(Value::Int(7)));
+        assert_eq!(
+            call("int", &[Value::Null]),
+            Err("Type error: int expects a string or an int, got null".to_string())
+        );
+    }
+
+    #[test]
+    fn typeof_names_each_type() {
+        let cases = vec![
+            (Value::Int(1), "int"),
+            (Value::Str("x".to_string()), "string"),
+            (Value::Bool(true), "bool"),
+            (Value::Null, "null"),
+            (Value::Char('c'), "char"),
+        ];
+        for (x, expected) in cases {
+            assert_eq!(call("typeof", &[x]), Ok(Value::Str(expected.to_string())));
+        }
+    }
+
+    #[test]
+    fn concat_joins_strings() {
+        let args: Vec<Value> = ["a", "b", "c"]
+            .iter()
+            .map(|x| Value::Str(x.to_string()))
+            .collect();
+        assert_eq!(call("concat", &args), Ok(Value::Str("abc".to_string())));
+        assert_eq!(call("concat", &[]), Ok(Value::Str(String::new())));
+        assert_eq!(
+            call("concat", &[Value::Str("a".to_string()), Value::Int(1)]),
+            Err("Type error: concat expects strings, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn println_formats_placeholders() {
+        let mut out = Vec::new();
+        let mut host = Host {
+            out: &mut out,
+            err: &mut Vec::new(),
+            clock: &|| 0,
+        };
+        let args = [
+            Value::Str("{} + {}".to_string()),
+            Value::Int(1),
+            Value::Int(2),
+        ];
+        assert_eq!(println(&mut host, &args), Ok(Value::Null));
+        // 没有占位符时以空格连接
+        assert_eq!(
+            println(&mut host, &[Value::Int(1), Value::Int(2)]),
+            Ok(Value::Null)
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), "1 + 2\n1 2\n");
+
+        assert_eq!(
+            call("println", &[Value::Str("{} {}".to_string()), Value::Int(1)]),
+            Err("format string has 2 placeholder(s), got 1 argument(s)".to_string())
+        );
+    }
+
+    #[test]
+    fn abs_min_max() {
+        let ints = |xs: &[i64]| -> Vec<Value> { xs.iter().map(|x| Value::Int(*x)).collect() };
+        assert_eq!(call("abs", &ints(&[-3])), Ok(Value::Int(3)));
+        assert_eq!(call("min", &ints(&[5, 2])), Ok(Value::Int(2)));
+        assert_eq!(call("max", &ints(&[1, 9, 4])), Ok(Value::Int(9)));
+        assert_eq!(
+            call("max", &ints(&[1])),
+            Err("max expects at least 2 arguments, got 1".to_string())
+        );
+        assert_eq!(
+            call("min", &[Value::Int(1), Value::Bool(true)]),
+            Err("Type error: min expects ints, got true".to_string())
+        );
+        assert_eq!(
+            call("abs", &ints(&[i64::MIN])),
+            Err("Integer overflow: abs(-9223372036854775808)".to_string())
+        );
+    }
+}
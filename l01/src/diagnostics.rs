@@ -0,0 +1,115 @@
+use std::fmt::{Display, Formatter};
+
+// 一条错误或警告，line 与 col 为问题所在的位置（从 1 开始）
+#[derive(Debug, Clone, PartialEq, Eq, new)]
+pub struct Diagnostic {
+    pub message: String,
+    pub line: u64,
+    pub col: u64,
+}
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}:{}", self.message, self.line, self.col)
+    }
+}
+impl Diagnostic {
+    // 从形如 "unknown statement starting with Identifier 'x' at 1:2" 的错误信息中
+    // 取出第一个 " at 行:列" 作为位置，其余部分作为信息；没有位置时返回 None
+    pub fn from_message(message: &str) -> Option<Diagnostic> {
+        let mut rest = message;
+        while let Some(i) = rest.find(" at ") {
+            let start = message.len() - rest.len() + i;
+            let position = &rest[i + 4..];
+            let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let line_end = digits(position);
+            if position[line_end..].starts_with(':') {
+                let end = line_end + 1 + digits(&position[line_end + 1..]);
+                let line = position[..line_end].parse();
+                let col = position[line_end + 1..end].parse();
+                if let (Ok(line), Ok(col)) = (line, col) {
+                    let text = format!("{}{}", &message[..start], &position[end..]);
+                    return Some(Diagnostic::new(text, line, col));
+                }
+            }
+            rest = &rest[i + 4..];
+        }
+        None
+    }
+}
+
+/**
+ * 编译过程中收集到的问题
+ * errors 会导致编译失败，warnings 只需要提示给用户，不影响程序的运行
+ */
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+}
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics::default()
+    }
+
+    pub fn error(&mut self, message: String, line: u64, col: u64) {
+        self.errors.push(Diagnostic::new(message, line, col));
+    }
+
+    pub fn warning(&mut self, message: String, line: u64, col: u64) {
+        self.warnings.push(Diagnostic::new(message, line, col));
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    // 把错误与警告分别按所在的位置（先行后列）排序，使编辑器可以从上到下显示
+    // 收集的顺序是遍历语法树的顺序，例如 f(g()) 中 g 的问题会先于 f 被发现
+    // 位置相同的问题保持原来的顺序
+    pub fn sort_by_position(&mut self) {
+        self.errors.sort_by_key(|x| (x.line, x.col));
+        self.warnings.sort_by_key(|x| (x.line, x.col));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_by_line_then_col() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.error("c".to_string(), 2, 1);
+        diagnostics.error("b".to_string(), 1, 9);
+        diagnostics.error("a".to_string(), 1, 2);
+        diagnostics.error("b2".to_string(), 1, 9);
+        diagnostics.sort_by_position();
+        let messages: Vec<&str> = diagnostics
+            .errors
+            .iter()
+            .map(|x| x.message.as_str())
+            .collect();
+        assert_eq!(messages, vec!["a", "b", "b2", "c"]);
+    }
+
+    #[test]
+    fn takes_first_position_from_message() {
+        assert_eq!(
+            Diagnostic::from_message("unknown statement starting with Identifier 'x' at 1:2"),
+            Some(Diagnostic::new(
+                "unknown statement starting with Identifier 'x'".to_string(),
+                1,
+                2
+            ))
+        );
+        assert_eq!(
+            Diagnostic::from_message("unexpected end of input at 3:1: expect '}' opened at 1:9"),
+            Some(Diagnostic::new(
+                "unexpected end of input: expect '}' opened at 1:9".to_string(),
+                3,
+                1
+            ))
+        );
+        assert_eq!(Diagnostic::from_message("look at me"), None);
+    }
+}
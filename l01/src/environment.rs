@@ -0,0 +1,111 @@
+use crate::value::Value;
+use std::collections::HashMap;
+
+/**
+ * 变量的运行时环境
+ * 由若干层栈桢组成：第一层为全局变量，每次调用自定义函数时压入新的一层。
+ * 每层栈桢中又可以有多层作用域，进入语句块（如循环体）时压入新的作用域，离开时丢弃其中声明的变量。
+ * 查找变量时先由内向外查当前栈桢的各层作用域，再查全局变量，函数不能访问调用者的局部变量。
+ */
+#[derive(Debug, Clone)]
+pub struct Environment {
+    scopes: Vec<HashMap<String, Value>>,
+    frames: Vec<usize>, // 每层栈桢的第一个作用域在 scopes 中的下标，全局栈桢为 0
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::new()
+    }
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment {
+            scopes: vec![HashMap::new()],
+            frames: vec![0],
+        }
+    }
+
+    // 在当前作用域中声明变量，已经存在的同名变量会被覆盖
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.current_mut().insert(name.to_string(), value);
+    }
+
+    // 修改已经声明的变量，修改的是查找时找到的那个变量；变量不存在时返回 false
+    pub fn assign(&mut self, name: &str, value: Value) -> bool {
+        let start = self.frame_start();
+        let scope = match self.scopes[start..]
+            .iter()
+            .rposition(|x| x.contains_key(name))
+        {
+            Some(i) => start + i,
+            None if self.scopes[0].contains_key(name) => 0,
+            None => return false,
+        };
+        self.scopes[scope].insert(name.to_string(), value);
+        true
+    }
+
+    // 查找变量的值
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.scopes[self.frame_start()..]
+            .iter()
+            .rev()
+            .find_map(|x| x.get(name))
+            .or_else(|| self.scopes[0].get(name))
+    }
+
+    // 当前栈桢中所有变量绑定的拷贝，供调试器等宿主程序在语句之间查看状态
+    // 内层作用域中的变量会遮盖外层的同名变量
+    pub fn snapshot(&self) -> HashMap<String, Value> {
+        let mut bindings = HashMap::new();
+        for x in &self.scopes[self.frame_start()..] {
+            bindings.extend(x.iter().map(|(k, v)| (k.to_string(), v.clone())));
+        }
+        bindings
+    }
+
+    // 用 snapshot 得到的绑定替换当前栈桢中的所有变量
+    pub fn restore(&mut self, bindings: HashMap<String, Value>) {
+        let start = self.frame_start();
+        self.scopes.truncate(start + 1);
+        self.scopes[start] = bindings;
+    }
+
+    // 调用自定义函数时进入新的栈桢
+    pub(crate) fn push_frame(&mut self) {
+        self.frames.push(self.scopes.len());
+        self.scopes.push(HashMap::new());
+    }
+
+    // 函数返回时退出栈桢，全局栈桢不会被退出
+    pub(crate) fn pop_frame(&mut self) {
+        if self.frames.len() > 1 {
+            let start = self.frames.pop().unwrap_or(1);
+            self.scopes.truncate(start);
+        }
+    }
+
+    // 进入语句块时进入新的作用域
+    pub(crate) fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    // 离开语句块时退出作用域，栈桢的第一个作用域不会被退出
+    pub(crate) fn pop_scope(&mut self) {
+        if self.scopes.len() > self.frame_start() + 1 {
+            self.scopes.pop();
+        }
+    }
+
+    fn frame_start(&self) -> usize {
+        self.frames.last().copied().unwrap_or(0)
+    }
+
+    fn current_mut(&mut self) -> &mut HashMap<String, Value> {
+        self.scopes
+            .last_mut()
+            .expect("the global scope is never popped")
+    }
+}
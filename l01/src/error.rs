@@ -1,15 +1,18 @@
+use crate::token::Token;
 use std::fmt::{Display, Formatter};
 
 #[derive(Debug)]
 pub enum DecodeError {
     TryNext,
     Fatal(String),
+    Parse(ParseError), // 期望的 Token 与实际读到的不符
 }
 impl Display for DecodeError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             DecodeError::TryNext => write!(f, "Please try next method"),
             DecodeError::Fatal(message) => write!(f, "{}", message),
+            DecodeError::Parse(e) => write!(f, "{}", e),
         }
     }
 }
@@ -23,3 +26,180 @@ impl From<String> for DecodeError {
         DecodeError::Fatal(message)
     }
 }
+impl From<std::fmt::Error> for DecodeError {
+    fn from(e: std::fmt::Error) -> Self {
+        DecodeError::Fatal(e.to_string())
+    }
+}
+// 词法错误在语法分析中是致命的，消息中保留错误的位置
+impl From<LexError> for DecodeError {
+    fn from(e: LexError) -> Self {
+        DecodeError::Fatal(e.to_string())
+    }
+}
+impl From<ParseError> for DecodeError {
+    fn from(e: ParseError) -> Self {
+        DecodeError::Parse(e)
+    }
+}
+impl std::error::Error for DecodeError {}
+
+// 语法错误：期望 expected 但读到了 found，line 与 col 为 found 的位置
+// 工具可以据此判断缺少的是什么，而不必解析错误信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub expected: String,
+    pub found: Token,
+    pub line: u64,
+    pub col: u64,
+}
+impl ParseError {
+    pub fn new(expected: &str, found: &Token) -> ParseError {
+        ParseError {
+            expected: expected.to_string(),
+            found: found.clone(),
+            line: found.line,
+            col: found.col,
+        }
+    }
+}
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expect {} but got {}", self.expected, self.found)
+    }
+}
+impl std::error::Error for ParseError {}
+
+// 词法错误的种类
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexErrorKind {
+    UnterminatedString,   // 字符串字面量没有闭合（遇到换行或 EOF）
+    UnterminatedChar,     // 字符字面量没有闭合
+    EmptyChar,            // 空的字符字面量 ''
+    MultiChar,            // 包含多个字符的字符字面量，如 'ab'
+    UnterminatedComment,  // 段注释一直到 EOF 都没有 */
+    InvalidEscape(char),  // 不支持的转义字符，如 \q
+    UnexpectedChar(char), // 无法识别的字符
+    InvalidNumber,        // 非法的数字字面量，如 123abc
+    TooManyTokens(usize), // Token 数量超过了上限
+    Io(String),           // 读取源代码失败
+}
+impl Display for LexErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexErrorKind::UnterminatedString => write!(f, "Unterminated string literal"),
+            LexErrorKind::UnterminatedChar => write!(f, "Unterminated char literal"),
+            LexErrorKind::EmptyChar => write!(f, "Empty char literal"),
+            LexErrorKind::MultiChar => write!(f, "Char literal must contain exactly one char"),
+            LexErrorKind::UnterminatedComment => write!(f, "No */ found until EOF"),
+            LexErrorKind::InvalidEscape(c) => write!(f, "Invalid escape \\{}", c),
+            LexErrorKind::UnexpectedChar(c) => write!(f, "Invalid token {}", c),
+            LexErrorKind::InvalidNumber => write!(f, "Invalid number"),
+            LexErrorKind::TooManyTokens(max) => write!(f, "Too many tokens (max {})", max),
+            LexErrorKind::Io(e) => write!(f, "Cannot read source: {}", e),
+        }
+    }
+}
+
+// 词法错误，line 与 col 为出错的位置（从 1 开始）
+#[derive(Debug, Clone, PartialEq, Eq, new)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub line: u64,
+    pub col: u64,
+}
+impl Display for LexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}:{}", self.kind, self.line, self.col)
+    }
+}
+impl std::error::Error for LexError {}
+
+// 运行时错误，position 为出错的函数调用所在的位置（行、列），无法确定时为 None
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub position: Option<(u64, u64)>,
+}
+impl RuntimeError {
+    pub fn new(message: String) -> RuntimeError {
+        RuntimeError {
+            message,
+            position: None,
+        }
+    }
+
+    pub fn at(message: String, line: u64, col: u64) -> RuntimeError {
+        RuntimeError {
+            message,
+            position: Some((line, col)),
+        }
+    }
+}
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.position {
+            Some((line, col)) => write!(f, "{} at {}:{}", self.message, line, col),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+impl From<String> for RuntimeError {
+    fn from(message: String) -> Self {
+        RuntimeError::new(message)
+    }
+}
+impl std::error::Error for RuntimeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_str;
+    use crate::token::TokenKind;
+    use std::error::Error;
+
+    // 在返回 Box<dyn Error> 的函数中用 ? 传递 DecodeError
+    fn parse_boxed(code: &str) -> Result<(), Box<dyn Error>> {
+        parse_str(code)?;
+        Ok(())
+    }
+
+    #[test]
+    fn decode_error_is_std_error() {
+        assert!(parse_boxed("sayHello();").is_ok());
+        let e = parse_boxed("sayHello(;").unwrap_err();
+        assert!(e.downcast_ref::<DecodeError>().is_some());
+        assert_eq!(
+            e.to_string(),
+            "expect expression but got Seperator ';' at 1:10"
+        );
+    }
+
+    #[test]
+    fn fmt_error_converts_to_decode_error() {
+        let e: DecodeError = std::fmt::Error.into();
+        assert!(matches!(e, DecodeError::Fatal(_)));
+    }
+
+    #[test]
+    fn lex_error_propagates_through_parse_str() {
+        let e = parse_str("println(\"abc);").unwrap_err();
+        assert!(matches!(e, DecodeError::Fatal(_)));
+        assert_eq!(e.to_string(), "Unterminated string literal at 1:9");
+
+        let lex = LexError::new(LexErrorKind::UnexpectedChar('@'), 2, 3);
+        assert_eq!(DecodeError::from(lex.clone()).to_string(), lex.to_string());
+    }
+
+    #[test]
+    fn parse_error_records_expected_and_found() {
+        let e = match parse_str("function f {}").unwrap_err() {
+            DecodeError::Parse(e) => e,
+            e => panic!("unexpected error {:?}", e),
+        };
+        assert_eq!(e.expected, "Seperator '('");
+        assert_eq!(e.found.kind, TokenKind::Seperator);
+        assert_eq!(&*e.found.text, "{");
+        assert_eq!((e.line, e.col), (1, 12));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::error::{LexError, LexErrorKind};
+
+// 字符串与字符字面量中 \ 后的转义字符，返回其代表的字符
+pub fn escape_char(c: char) -> Option<char> {
+    match c {
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        '\\' => Some('\\'),
+        '"' => Some('"'),
+        '\'' => Some('\''),
+        _ => None,
+    }
+}
+
+/**
+ * 按字符串字面量的规则对 s 做反转义，s 为两侧引号之间的原文
+ * 支持 \n \t \\ \" \'，以及续行：\ 后紧跟换行（\n 或 \r\n）时，忽略换行以及下一行开头的空格与制表符
+ * 出错时 LexError 中的位置相对于 s 的开头（第 1 行第 1 列）
+ */
+pub fn unescape(s: &str) -> Result<String, LexError> {
+    unescape_with(s, false)
+}
+
+// 与 unescape 相同，但遇到未知的转义时不报错，而是原样保留 \ 与其后的字符，如 \q 仍为 \q
+pub fn unescape_lenient(s: &str) -> Result<String, LexError> {
+    unescape_with(s, true)
+}
+
+fn unescape_with(s: &str, lenient: bool) -> Result<String, LexError> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    let (mut line, mut col) = (1, 0);
+
+    while let Some(c) = chars.next() {
+        col += 1;
+        if c == '\n' {
+            line += 1;
+            col = 0;
+        }
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        let (escape_line, escape_col) = (line, col);
+        match chars.next() {
+            Some(x) if escape_char(x).is_some() => {
+                col += 1;
+                out.extend(escape_char(x));
+            }
+            Some(x @ '\r') | Some(x @ '\n') => {
+                if x == '\r' && chars.next() != Some('\n') {
+                    return Err(LexError::new(
+                        LexErrorKind::InvalidEscape('\r'),
+                        escape_line,
+                        escape_col,
+                    ));
+                }
+                line += 1;
+                col = 0;
+                while let Some(' ') | Some('\t') = chars.peek() {
+                    chars.next();
+                    col += 1;
+                }
+            }
+            Some(x) if lenient => {
+                col += 1;
+                out.push('\\');
+                out.push(x);
+            }
+            Some(x) => {
+                return Err(LexError::new(
+                    LexErrorKind::InvalidEscape(x),
+                    escape_line,
+                    escape_col,
+                ))
+            }
+            None => {
+                return Err(LexError::new(
+                    LexErrorKind::UnterminatedString,
+                    escape_line,
+                    escape_col,
+                ))
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescapes_string_literal_text() {
+        assert_eq!(unescape("a\\nb"), Ok("a\nb".to_string()));
+        assert_eq!(unescape("\\\\"), Ok("\\".to_string()));
+        assert_eq!(unescape("say \\\"hi\\\""), Ok("say \"hi\"".to_string()));
+        assert_eq!(
+            unescape("ab\\q"),
+            Err(LexError::new(LexErrorKind::InvalidEscape('q'), 1, 3))
+        );
+        assert_eq!(unescape_lenient("ab\\q"), Ok("ab\\q".to_string()));
+    }
+
+    #[test]
+    fn lenient_unescape_keeps_unknown_sequences() {
+        assert_eq!(unescape_lenient("ab\\q"), Ok("ab\\q".to_string()));
+        assert_eq!(unescape_lenient("a\\nb"), Ok("a\nb".to_string()));
+    }
+}
@@ -1,19 +1,133 @@
-use std::ptr::NonNull;
+use crate::builtins;
+use crate::json::{Json, ToJson};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter, Write};
+use std::rc::Rc;
+
+// 语法树节点的编号，由语法分析器按解析完成的顺序从 1 开始分配，同一段源代码每次解析得到的编号相同
+// 不是由语法分析器创建的节点编号为 0
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub u32);
+
+impl Display for NodeId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+// 语法树节点到源代码字节范围 [start, end) 的映射，供报错、代码高亮等工具定位节点
+// 范围从节点的第一个 Token 开始，到节点的最后一个 Token 结束
+pub type SourceMap = HashMap<NodeId, (usize, usize)>;
+
+// 默认的每一级缩进
+pub const DEFAULT_INDENT: &str = "\t";
 
 pub trait Dumper {
-    //打印对象信息，prefix是前面填充的字符串，通常用于缩进显示
-    fn dump(&self, prefix: &str);
+    //把对象信息写入 w，prefix是前面填充的字符串，indent是每一级缩进使用的字符串
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result;
+
+    //打印对象信息到标准输出，使用默认的缩进
+    fn dump(&self, prefix: &str) {
+        print!("{}", self.dump_string(prefix, DEFAULT_INDENT));
+    }
+
+    //以字符串的形式返回对象信息，输出是确定的，可用于比对
+    fn dump_string(&self, prefix: &str, indent: &str) -> String {
+        let mut s = String::new();
+        self.dump_to(&mut s, prefix, indent)
+            .expect("writing to a String never fails");
+        s
+    }
 }
 
+// 下一级缩进
+pub(crate) fn next_level(prefix: &str, indent: &str) -> String {
+    prefix.to_string() + indent
+}
+
+#[derive(Debug, Clone)]
 pub enum Statement {
     FunctionDecl(FunctionDecl),
     FunctionCall(FunctionCall),
+    VariableDecl(VariableDecl),
+    Return(ReturnStatement),
+    While(WhileStatement),
+    For(ForStatement),
+    Assignment(Assignment),
+    Import(ImportStatement),
+    ExpressionStatement(Expression, NodeId), // 以分号结尾的表达式，其值被丢弃
+    Empty(NodeId),                           // 单独的分号，什么也不做
+}
+impl PartialEq for Statement {
+    // 只比较语法结构，忽略位置与节点编号
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Statement::FunctionDecl(a), Statement::FunctionDecl(b)) => a == b,
+            (Statement::FunctionCall(a), Statement::FunctionCall(b)) => a == b,
+            (Statement::VariableDecl(a), Statement::VariableDecl(b)) => a == b,
+            (Statement::Return(a), Statement::Return(b)) => a == b,
+            (Statement::While(a), Statement::While(b)) => a == b,
+            (Statement::For(a), Statement::For(b)) => a == b,
+            (Statement::Assignment(a), Statement::Assignment(b)) => a == b,
+            (Statement::Import(a), Statement::Import(b)) => a == b,
+            (Statement::ExpressionStatement(a, _), Statement::ExpressionStatement(b, _)) => a == b,
+            (Statement::Empty(_), Statement::Empty(_)) => true,
+            _ => false,
+        }
+    }
+}
+impl Statement {
+    // 语句的节点编号
+    pub fn id(&self) -> NodeId {
+        match self {
+            Statement::FunctionDecl(x) => x.id,
+            Statement::FunctionCall(x) => x.id,
+            Statement::VariableDecl(x) => x.id,
+            Statement::Return(x) => x.id,
+            Statement::While(x) => x.id,
+            Statement::For(x) => x.id,
+            Statement::Assignment(x) => x.id,
+            Statement::Import(x) => x.id,
+            Statement::ExpressionStatement(_, id) | Statement::Empty(id) => *id,
+        }
+    }
 }
 impl Dumper for Statement {
-    fn dump(&self, prefix: &str) {
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result {
+        match self {
+            Statement::FunctionDecl(x) => x.dump_to(w, prefix, indent),
+            Statement::FunctionCall(x) => x.dump_to(w, prefix, indent),
+            Statement::VariableDecl(x) => x.dump_to(w, prefix, indent),
+            Statement::Return(x) => x.dump_to(w, prefix, indent),
+            Statement::While(x) => x.dump_to(w, prefix, indent),
+            Statement::For(x) => x.dump_to(w, prefix, indent),
+            Statement::Assignment(x) => x.dump_to(w, prefix, indent),
+            Statement::Import(x) => x.dump_to(w, prefix, indent),
+            Statement::ExpressionStatement(x, _) => {
+                writeln!(w, "{}ExpressionStatement", prefix)?;
+                x.dump_to(w, &next_level(prefix, indent), indent)
+            }
+            Statement::Empty(_) => writeln!(w, "{}Empty", prefix),
+        }
+    }
+}
+
+impl ToJson for Statement {
+    fn to_json(&self) -> Json {
         match self {
-            Statement::FunctionDecl(x) => x.dump(prefix),
-            Statement::FunctionCall(x) => x.dump(prefix),
+            Statement::FunctionDecl(x) => x.to_json(),
+            Statement::FunctionCall(x) => x.to_json(),
+            Statement::VariableDecl(x) => x.to_json(),
+            Statement::Return(x) => x.to_json(),
+            Statement::While(x) => x.to_json(),
+            Statement::For(x) => x.to_json(),
+            Statement::Assignment(x) => x.to_json(),
+            Statement::Import(x) => x.to_json(),
+            Statement::ExpressionStatement(x, _) => Json::Object(vec![
+                ("type", Json::Str("ExpressionStatement".to_string())),
+                ("expression", x.to_json()),
+            ]),
+            Statement::Empty(_) => Json::Object(vec![("type", Json::Str("Empty".to_string()))]),
         }
     }
 }
@@ -21,73 +135,876 @@ impl Dumper for Statement {
 /**
  * 函数声明节点
  */
+#[derive(Debug, Clone)]
 pub struct FunctionDecl {
-    pub name: String,       //函数名称
-    pub body: FunctionBody, //函数体
+    pub name: Rc<str>,       //函数名称，与 Token 共享驻留的字符串
+    pub params: Vec<String>, //参数名称
+    pub body: FunctionBody,  //函数体
+    pub line: u64,           //function 关键字所在的位置
+    pub col: u64,
+    pub id: NodeId,
+}
+impl PartialEq for FunctionDecl {
+    // 只比较语法结构，忽略位置与节点编号
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.params == other.params && self.body == other.body
+    }
 }
 impl FunctionDecl {
-    pub fn new(name: String, body: FunctionBody) -> FunctionDecl {
-        FunctionDecl { name, body }
+    pub fn new(
+        name: Rc<str>,
+        params: Vec<String>,
+        body: FunctionBody,
+        line: u64,
+        col: u64,
+    ) -> FunctionDecl {
+        FunctionDecl {
+            name,
+            params,
+            body,
+            line,
+            col,
+            id: NodeId::default(),
+        }
     }
 }
 impl Dumper for FunctionDecl {
-    fn dump(&self, prefix: &str) {
-        println!("{}FunctionDecl {}", prefix, self.name);
-        self.body.dump(&(prefix.to_string() + "\t"));
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result {
+        if self.params.is_empty() {
+            writeln!(w, "{}FunctionDecl {}", prefix, self.name)?;
+        } else {
+            writeln!(
+                w,
+                "{}FunctionDecl {}, params: {}",
+                prefix,
+                self.name,
+                self.params.join(", ")
+            )?;
+        }
+        self.body.dump_to(w, &next_level(prefix, indent), indent)
+    }
+}
+
+impl ToJson for FunctionDecl {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("type", Json::Str("FunctionDecl".to_string())),
+            ("name", Json::Str(self.name.to_string())),
+            (
+                "params",
+                Json::Array(
+                    self.params
+                        .iter()
+                        .map(|x| Json::Str(x.to_string()))
+                        .collect(),
+                ),
+            ),
+            ("body", self.body.to_json()),
+        ])
+    }
+}
+
+/**
+ * 变量声明节点
+ */
+#[derive(Debug, Clone)]
+pub struct VariableDecl {
+    pub name: String,             //变量名称
+    pub init: Option<Expression>, //初始值，没有时为 null
+    pub line: u64,                //let 关键字所在的位置
+    pub col: u64,
+    pub id: NodeId,
+}
+impl PartialEq for VariableDecl {
+    // 只比较语法结构，忽略位置与节点编号
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.init == other.init
+    }
+}
+impl VariableDecl {
+    pub fn new(name: String, init: Option<Expression>, line: u64, col: u64) -> VariableDecl {
+        VariableDecl {
+            name,
+            init,
+            line,
+            col,
+            id: NodeId::default(),
+        }
+    }
+}
+impl Dumper for VariableDecl {
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result {
+        writeln!(w, "{}VariableDecl {}", prefix, self.name)?;
+        match &self.init {
+            Some(x) => x.dump_to(w, &next_level(prefix, indent), indent),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ToJson for VariableDecl {
+    fn to_json(&self) -> Json {
+        let mut fields = vec![
+            ("type", Json::Str("VariableDecl".to_string())),
+            ("name", Json::Str(self.name.to_string())),
+        ];
+        if let Some(x) = &self.init {
+            fields.push(("init", x.to_json()));
+        }
+        Json::Object(fields)
+    }
+}
+
+/**
+ * return 语句
+ */
+#[derive(Debug, Clone)]
+pub struct ReturnStatement {
+    pub value: Option<Expression>, //返回值，没有时为 null
+    pub line: u64,                 //return 关键字所在的位置
+    pub col: u64,
+    pub id: NodeId,
+}
+impl PartialEq for ReturnStatement {
+    // 只比较语法结构，忽略位置与节点编号
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+impl ReturnStatement {
+    pub fn new(value: Option<Expression>, line: u64, col: u64) -> ReturnStatement {
+        ReturnStatement {
+            value,
+            line,
+            col,
+            id: NodeId::default(),
+        }
+    }
+}
+impl Dumper for ReturnStatement {
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result {
+        writeln!(w, "{}Return", prefix)?;
+        match &self.value {
+            Some(x) => x.dump_to(w, &next_level(prefix, indent), indent),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ToJson for ReturnStatement {
+    fn to_json(&self) -> Json {
+        let mut fields = vec![("type", Json::Str("Return".to_string()))];
+        if let Some(x) = &self.value {
+            fields.push(("value", x.to_json()));
+        }
+        Json::Object(fields)
+    }
+}
+
+/**
+ * import 语句，只能出现在顶层
+ */
+#[derive(Debug, Clone)]
+pub struct ImportStatement {
+    pub path: String, //被导入文件的路径
+    pub line: u64,    //import 关键字所在的位置
+    pub col: u64,
+    pub id: NodeId,
+}
+impl PartialEq for ImportStatement {
+    // 只比较语法结构，忽略位置与节点编号
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+impl ImportStatement {
+    pub fn new(path: String, line: u64, col: u64) -> ImportStatement {
+        ImportStatement {
+            path,
+            line,
+            col,
+            id: NodeId::default(),
+        }
+    }
+}
+impl Dumper for ImportStatement {
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, _indent: &str) -> fmt::Result {
+        writeln!(w, "{}Import {:?}", prefix, self.path)
+    }
+}
+
+impl ToJson for ImportStatement {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("type", Json::Str("Import".to_string())),
+            ("path", Json::Str(self.path.to_string())),
+        ])
+    }
+}
+
+/**
+ * while 循环
+ */
+#[derive(Debug, Clone)]
+pub struct WhileStatement {
+    pub condition: Expression, //循环条件，必须为布尔值
+    pub body: Block,           //循环体
+    pub line: u64,             //while 关键字所在的位置
+    pub col: u64,
+    pub id: NodeId,
+}
+impl PartialEq for WhileStatement {
+    // 只比较语法结构，忽略位置与节点编号
+    fn eq(&self, other: &Self) -> bool {
+        self.condition == other.condition && self.body == other.body
+    }
+}
+impl WhileStatement {
+    pub fn new(condition: Expression, body: Block, line: u64, col: u64) -> WhileStatement {
+        WhileStatement {
+            condition,
+            body,
+            line,
+            col,
+            id: NodeId::default(),
+        }
+    }
+}
+impl Dumper for WhileStatement {
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result {
+        writeln!(w, "{}While", prefix)?;
+        self.condition
+            .dump_to(w, &next_level(prefix, indent), indent)?;
+        self.body.dump_to(w, &next_level(prefix, indent), indent)
+    }
+}
+
+impl ToJson for WhileStatement {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("type", Json::Str("While".to_string())),
+            ("condition", self.condition.to_json()),
+            ("body", self.body.to_json()),
+        ])
+    }
+}
+
+/**
+ * for 循环
+ * init 在循环开始前执行一次，每次循环前检查 condition（没有时视为 true），每次循环后执行 update
+ * init 中声明的变量只在循环内可见
+ */
+#[derive(Debug, Clone)]
+pub struct ForStatement {
+    pub init: Option<Box<Statement>>,   //变量声明、赋值或表达式语句
+    pub condition: Option<Expression>,  //循环条件，必须为布尔值
+    pub update: Option<Box<Statement>>, //赋值或表达式语句
+    pub body: Block,                    //循环体
+    pub line: u64,                      //for 关键字所在的位置
+    pub col: u64,
+    pub id: NodeId,
+}
+impl PartialEq for ForStatement {
+    // 只比较语法结构，忽略位置与节点编号
+    fn eq(&self, other: &Self) -> bool {
+        self.init == other.init
+            && self.condition == other.condition
+            && self.update == other.update
+            && self.body == other.body
+    }
+}
+impl ForStatement {
+    pub fn new(
+        init: Option<Statement>,
+        condition: Option<Expression>,
+        update: Option<Statement>,
+        body: Block,
+        line: u64,
+        col: u64,
+    ) -> ForStatement {
+        ForStatement {
+            init: init.map(Box::new),
+            condition,
+            update: update.map(Box::new),
+            body,
+            line,
+            col,
+            id: NodeId::default(),
+        }
+    }
+}
+impl Dumper for ForStatement {
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result {
+        writeln!(w, "{}For", prefix)?;
+        let prefix = next_level(prefix, indent);
+        if let Some(x) = &self.init {
+            writeln!(w, "{}Init", prefix)?;
+            x.dump_to(w, &next_level(&prefix, indent), indent)?;
+        }
+        if let Some(x) = &self.condition {
+            writeln!(w, "{}Condition", prefix)?;
+            x.dump_to(w, &next_level(&prefix, indent), indent)?;
+        }
+        if let Some(x) = &self.update {
+            writeln!(w, "{}Update", prefix)?;
+            x.dump_to(w, &next_level(&prefix, indent), indent)?;
+        }
+        self.body.dump_to(w, &prefix, indent)
+    }
+}
+
+impl ToJson for ForStatement {
+    fn to_json(&self) -> Json {
+        let mut fields = vec![("type", Json::Str("For".to_string()))];
+        if let Some(x) = &self.init {
+            fields.push(("init", x.to_json()));
+        }
+        if let Some(x) = &self.condition {
+            fields.push(("condition", x.to_json()));
+        }
+        if let Some(x) = &self.update {
+            fields.push(("update", x.to_json()));
+        }
+        fields.push(("body", self.body.to_json()));
+        Json::Object(fields)
+    }
+}
+
+/**
+ * 赋值，修改已经声明的变量
+ */
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    pub name: String,      //变量名称
+    pub value: Expression, //新的值
+    pub line: u64,         //变量名所在的位置
+    pub col: u64,
+    pub id: NodeId,
+}
+impl PartialEq for Assignment {
+    // 只比较语法结构，忽略位置与节点编号
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.value == other.value
+    }
+}
+impl Assignment {
+    pub fn new(name: String, value: Expression, line: u64, col: u64) -> Assignment {
+        Assignment {
+            name,
+            value,
+            line,
+            col,
+            id: NodeId::default(),
+        }
+    }
+}
+impl Dumper for Assignment {
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result {
+        writeln!(w, "{}Assignment {}", prefix, self.name)?;
+        self.value.dump_to(w, &next_level(prefix, indent), indent)
+    }
+}
+
+impl ToJson for Assignment {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("type", Json::Str("Assignment".to_string())),
+            ("name", Json::Str(self.name.to_string())),
+            ("value", self.value.to_json()),
+        ])
+    }
+}
+
+/**
+ * 语句块，即循环体等由 {} 括起来的一组语句
+ * 与函数体不同，语句块中不能声明函数，其中声明的变量只在块内可见
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub stmts: Vec<Statement>,
+}
+impl Block {
+    pub fn new(stmts: Vec<Statement>) -> Block {
+        Block { stmts }
+    }
+}
+impl Dumper for Block {
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result {
+        writeln!(w, "{}Block", prefix)?;
+        for x in &self.stmts {
+            x.dump_to(w, &next_level(prefix, indent), indent)?;
+        }
+        Ok(())
+    }
+}
+
+impl ToJson for Block {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("type", Json::Str("Block".to_string())),
+            (
+                "stmts",
+                Json::Array(self.stmts.iter().map(|x| x.to_json()).collect()),
+            ),
+        ])
     }
 }
 
 /**
  * 函数体
  */
+#[derive(Debug, Clone, PartialEq)]
 pub struct FunctionBody {
-    pub stmts: Vec<FunctionCall>,
+    pub stmts: Vec<Statement>,
 }
 impl FunctionBody {
-    pub fn new(stmts: Vec<FunctionCall>) -> FunctionBody {
+    pub fn new(stmts: Vec<Statement>) -> FunctionBody {
         FunctionBody { stmts }
     }
+
+    // 函数体中是否没有任何语句（只有空语句 ; 也算作空），这样的函数调用时什么都不做，返回 null
+    pub fn is_empty(&self) -> bool {
+        self.stmts.iter().all(|x| matches!(x, Statement::Empty(_)))
+    }
 }
 impl Dumper for FunctionBody {
-    fn dump(&self, prefix: &str) {
-        println!("{}FunctionBody", prefix);
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result {
+        writeln!(w, "{}FunctionBody", prefix)?;
         for x in &self.stmts {
-            x.dump(&*format!("{}\t", prefix))
+            x.dump_to(w, &next_level(prefix, indent), indent)?;
         }
+        Ok(())
+    }
+}
+
+impl ToJson for FunctionBody {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("type", Json::Str("FunctionBody".to_string())),
+            (
+                "stmts",
+                Json::Array(self.stmts.iter().map(|x| x.to_json()).collect()),
+            ),
+        ])
     }
 }
 
 /**
  * 函数调用
  */
+#[derive(Debug, Clone)]
 pub struct FunctionCall {
-    pub name: String,
-    pub parameters: Vec<String>,
-    pub definition: Option<NonNull<FunctionDecl>>, // 指向函数的声明
+    pub name: Rc<str>, // 与 Token 共享驻留的字符串
+    pub parameters: Vec<Expression>,
+    // 指向函数的声明路径：第一个下标为 Prog::stmts 中的位置，
+    // 之后的每个下标为上一个函数的函数体中的位置，用于定位嵌套声明的函数
+    pub definition: Option<Vec<usize>>,
+    pub line: u64, // 函数名所在的位置
+    pub col: u64,
+    pub id: NodeId,
+}
+impl PartialEq for FunctionCall {
+    // 只比较语法结构，忽略位置与节点编号
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.parameters == other.parameters
+            && self.definition == other.definition
+    }
 }
 impl FunctionCall {
-    pub fn new(name: String, parameters: Vec<String>) -> FunctionCall {
+    pub fn new(name: Rc<str>, parameters: Vec<Expression>, line: u64, col: u64) -> FunctionCall {
         FunctionCall {
             name,
             parameters,
             definition: None,
+            line,
+            col,
+            id: NodeId::default(),
         }
     }
 }
+// 消解到自定义函数的调用标记为 resolved，内置函数标记为 builtin，
+// 其他的调用（尚未消解或消解失败）标记为 [UNRESOLVED]，便于在输出中查找
 impl Dumper for FunctionCall {
-    fn dump(&self, prefix: &str) {
-        println!(
-            "{}FunctionCall {}, {}",
-            prefix,
-            self.name,
-            match self.definition {
-                Some(_) => "resolved",
-                None => "not resolved",
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result {
+        match self.definition {
+            Some(_) => writeln!(w, "{}FunctionCall {}, resolved", prefix, self.name)?,
+            None if builtins::is_builtin(&self.name) => {
+                writeln!(w, "{}FunctionCall {}, builtin", prefix, self.name)?
             }
-        );
+            None => writeln!(w, "{}FunctionCall {} [UNRESOLVED]", prefix, self.name)?,
+        }
 
         for x in &self.parameters {
-            println!("{}\tParameter: {}", prefix, x)
+            x.dump_to(w, &next_level(prefix, indent), indent)?;
+        }
+        Ok(())
+    }
+}
+
+impl ToJson for FunctionCall {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("type", Json::Str("FunctionCall".to_string())),
+            ("name", Json::Str(self.name.to_string())),
+            (
+                "parameters",
+                Json::Array(self.parameters.iter().map(|x| x.to_json()).collect()),
+            ),
+            ("resolved", Json::Bool(self.definition.is_some())),
+        ])
+    }
+}
+
+// 二元运算符的左右结合力，右侧比左侧大 1 表示左结合；不是二元运算符时返回 None
+pub fn binding_power(op: &str) -> Option<(u8, u8)> {
+    let bp = match op {
+        "||" => (1, 2),
+        "&&" => (3, 4),
+        "==" | "!=" => (5, 6),
+        "<" | "<=" | ">" | ">=" => (7, 8),
+        "+" | "-" => (9, 10),
+        "*" | "/" | "%" => (11, 12),
+        _ => return None,
+    };
+    Some(bp)
+}
+
+/**
+ * 表达式
+ */
+#[derive(Debug, Clone)]
+pub enum Expression {
+    StringLiteral(String), // 字符串字面量
+    IntegerLiteral(i64),   // 整数字面量
+    Char(char),            // 字符字面量
+    Bool(bool),            // 布尔字面量 true / false
+    Variable(String),      // 变量引用
+    Call(FunctionCall),    // 函数调用
+    Unary {
+        op: String, // 运算符，! 或 -
+        operand: Box<Expression>,
+    },
+    Binary {
+        op: String, // 运算符，算术运算符 + - * / % 或比较运算符 == != < <= > >=
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+    Logical {
+        op: String, // 运算符，&& 或 ||，求值时短路
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+    Assign {
+        name: String, // 被赋值的变量
+        value: Box<Expression>,
+        line: u64, // = 所在的位置
+        col: u64,
+    },
+    Member {
+        target: Box<Expression>, // 被访问的对象
+        field: String,           // 成员的名称
+        line: u64,               // . 所在的位置
+        col: u64,
+    },
+    Index {
+        target: Box<Expression>, // 被索引的对象
+        index: Box<Expression>,  // 下标
+        line: u64,               // [ 所在的位置
+        col: u64,
+    },
+}
+impl PartialEq for Expression {
+    // 只比较语法结构，忽略位置
+    fn eq(&self, other: &Self) -> bool {
+        use Expression::*;
+        match (self, other) {
+            (StringLiteral(a), StringLiteral(b)) => a == b,
+            (IntegerLiteral(a), IntegerLiteral(b)) => a == b,
+            (Char(a), Char(b)) => a == b,
+            (Bool(a), Bool(b)) => a == b,
+            (Variable(a), Variable(b)) => a == b,
+            (Call(a), Call(b)) => a == b,
+            (
+                Unary { op, operand },
+                Unary {
+                    op: op2,
+                    operand: operand2,
+                },
+            ) => op == op2 && operand == operand2,
+            (
+                Binary { op, left, right },
+                Binary {
+                    op: op2,
+                    left: left2,
+                    right: right2,
+                },
+            )
+            | (
+                Logical { op, left, right },
+                Logical {
+                    op: op2,
+                    left: left2,
+                    right: right2,
+                },
+            ) => op == op2 && left == left2 && right == right2,
+            (
+                Assign { name, value, .. },
+                Assign {
+                    name: name2,
+                    value: value2,
+                    ..
+                },
+            ) => name == name2 && value == value2,
+            (
+                Member { target, field, .. },
+                Member {
+                    target: target2,
+                    field: field2,
+                    ..
+                },
+            ) => target == target2 && field == field2,
+            (
+                Index { target, index, .. },
+                Index {
+                    target: target2,
+                    index: index2,
+                    ..
+                },
+            ) => target == target2 && index == index2,
+            _ => false,
+        }
+    }
+}
+impl Dumper for Expression {
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result {
+        match self {
+            Expression::StringLiteral(x) => writeln!(w, "{}StringLiteral {:?}", prefix, x),
+            Expression::IntegerLiteral(x) => writeln!(w, "{}IntegerLiteral {}", prefix, x),
+            Expression::Char(x) => writeln!(w, "{}Char {:?}", prefix, x),
+            Expression::Bool(x) => writeln!(w, "{}Bool {}", prefix, x),
+            Expression::Variable(x) => writeln!(w, "{}Variable {}", prefix, x),
+            Expression::Call(x) => x.dump_to(w, prefix, indent),
+            Expression::Unary { op, operand } => {
+                writeln!(w, "{}Unary {}", prefix, op)?;
+                operand.dump_to(w, &next_level(prefix, indent), indent)
+            }
+            Expression::Binary { op, left, right } => {
+                writeln!(w, "{}Binary {}", prefix, op)?;
+                left.dump_to(w, &next_level(prefix, indent), indent)?;
+                right.dump_to(w, &next_level(prefix, indent), indent)
+            }
+            Expression::Logical { op, left, right } => {
+                writeln!(w, "{}Logical {}", prefix, op)?;
+                left.dump_to(w, &next_level(prefix, indent), indent)?;
+                right.dump_to(w, &next_level(prefix, indent), indent)
+            }
+            Expression::Assign { name, value, .. } => {
+                writeln!(w, "{}Assign {}", prefix, name)?;
+                value.dump_to(w, &next_level(prefix, indent), indent)
+            }
+            Expression::Member { target, field, .. } => {
+                writeln!(w, "{}Member {}", prefix, field)?;
+                target.dump_to(w, &next_level(prefix, indent), indent)
+            }
+            Expression::Index { target, index, .. } => {
+                writeln!(w, "{}Index", prefix)?;
+                target.dump_to(w, &next_level(prefix, indent), indent)?;
+                index.dump_to(w, &next_level(prefix, indent), indent)
+            }
+        }
+    }
+}
+impl ToJson for Expression {
+    fn to_json(&self) -> Json {
+        let node = |name: &str| ("type", Json::Str(name.to_string()));
+        match self {
+            Expression::StringLiteral(x) => Json::Object(vec![
+                node("StringLiteral"),
+                ("value", Json::Str(x.to_string())),
+            ]),
+            Expression::IntegerLiteral(x) => {
+                Json::Object(vec![node("IntegerLiteral"), ("value", Json::Int(*x))])
+            }
+            Expression::Char(x) => {
+                Json::Object(vec![node("Char"), ("value", Json::Str(x.to_string()))])
+            }
+            Expression::Bool(x) => Json::Object(vec![node("Bool"), ("value", Json::Bool(*x))]),
+            Expression::Variable(x) => {
+                Json::Object(vec![node("Variable"), ("name", Json::Str(x.to_string()))])
+            }
+            Expression::Call(x) => x.to_json(),
+            Expression::Unary { op, operand } => Json::Object(vec![
+                node("Unary"),
+                ("op", Json::Str(op.to_string())),
+                ("operand", operand.to_json()),
+            ]),
+            Expression::Binary { op, left, right } => Json::Object(vec![
+                node("Binary"),
+                ("op", Json::Str(op.to_string())),
+                ("left", left.to_json()),
+                ("right", right.to_json()),
+            ]),
+            Expression::Logical { op, left, right } => Json::Object(vec![
+                node("Logical"),
+                ("op", Json::Str(op.to_string())),
+                ("left", left.to_json()),
+                ("right", right.to_json()),
+            ]),
+            Expression::Assign { name, value, .. } => Json::Object(vec![
+                node("Assign"),
+                ("name", Json::Str(name.to_string())),
+                ("value", value.to_json()),
+            ]),
+            Expression::Member { target, field, .. } => Json::Object(vec![
+                node("Member"),
+                ("target", target.to_json()),
+                ("field", Json::Str(field.to_string())),
+            ]),
+            Expression::Index { target, index, .. } => Json::Object(vec![
+                node("Index"),
+                ("target", target.to_json()),
+                ("index", index.to_json()),
+            ]),
+        }
+    }
+}
+
+// 以源代码的形式输出表达式，只在优先级或结合性需要时才加括号
+// 例如 (2 + 3) * 4 会保留括号，而 2 + (3 * 4) 输出为 2 + 3 * 4
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::StringLiteral(x) => write!(f, "\"{}\"", escape_literal(x, '"')),
+            Expression::IntegerLiteral(x) => write!(f, "{}", x),
+            Expression::Char(x) => write!(f, "'{}'", escape_literal(&x.to_string(), '\'')),
+            Expression::Bool(x) => write!(f, "{}", x),
+            Expression::Variable(x) => write!(f, "{}", x),
+            Expression::Call(call) => {
+                write!(f, "{}(", call.name)?;
+                for (i, x) in call.parameters.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", x)?;
+                }
+                write!(f, ")")
+            }
+            Expression::Unary { op, operand } => {
+                // 运算数是二元运算时需要括号；连续的 - 会被当作 -- 运算符，也需要括号
+                let parens = match operand.as_ref() {
+                    Expression::Binary { .. }
+                    | Expression::Logical { .. }
+                    | Expression::Assign { .. } => true,
+                    Expression::Unary { op: x, .. } => x == "-" && op == "-",
+                    Expression::IntegerLiteral(x) => *x < 0 && op == "-",
+                    _ => false,
+                };
+                write!(f, "{}", op)?;
+                write_operand(f, operand, parens)
+            }
+            Expression::Binary { op, left, right } | Expression::Logical { op, left, right } => {
+                let (left_bp, right_bp) = binding_power(op).unwrap_or((0, 0));
+                // 左侧的运算结合力不如 op 时需要括号，如 (a + b) * c；
+                // 右侧的运算结合力不超过 op 时需要括号，如 a - (b - c)
+                let left_parens = operand_binding_power(left).is_some_and(|(_, r)| r <= left_bp);
+                let right_parens = operand_binding_power(right).is_some_and(|(l, _)| l < right_bp);
+                write_operand(f, left, left_parens)?;
+                write!(f, " {} ", op)?;
+                write_operand(f, right, right_parens)
+            }
+            Expression::Assign { name, value, .. } => write!(f, "{} = {}", name, value),
+            Expression::Member { target, field, .. } => {
+                write_operand(f, target, is_operation(target))?;
+                write!(f, ".{}", field)
+            }
+            Expression::Index { target, index, .. } => {
+                write_operand(f, target, is_operation(target))?;
+                write!(f, "[{}]", index)
+            }
+        }
+    }
+}
+
+// 是否为运算表达式
+// 成员访问与下标的结合力最强，对象是运算表达式时都需要括号，如 (a + b).c
+fn is_operation(expr: &Expression) -> bool {
+    matches!(
+        expr,
+        Expression::Unary { .. }
+            | Expression::Binary { .. }
+            | Expression::Logical { .. }
+            | Expression::Assign { .. }
+    )
+}
+
+// 二元运算表达式的结合力，其他表达式返回 None
+fn operand_binding_power(expr: &Expression) -> Option<(u8, u8)> {
+    match expr {
+        Expression::Binary { op, .. } | Expression::Logical { op, .. } => binding_power(op),
+        // 赋值的优先级最低，作为运算数时总是需要括号
+        Expression::Assign { .. } => Some((0, 0)),
+        _ => None,
+    }
+}
+
+fn write_operand(f: &mut Formatter<'_>, expr: &Expression, parens: bool) -> fmt::Result {
+    if parens {
+        write!(f, "({})", expr)
+    } else {
+        write!(f, "{}", expr)
+    }
+}
+
+// 把字面量中需要转义的字符还原为转义序列，quote 为字面量两侧的引号
+fn escape_literal(s: &str, quote: char) -> String {
+    let mut result = String::new();
+    for c in s.chars() {
+        match c {
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\\' => result.push_str("\\\\"),
+            c if c == quote => {
+                result.push('\\');
+                result.push(c);
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::{tokenize, TokenBuffer};
+    use crate::parser::Parser;
+
+    // 解析表达式后再输出为源代码
+    fn round_trip(src: &str) -> String {
+        let tokens = tokenize(src).unwrap();
+        Parser::new(TokenBuffer::new(tokens.into_iter()))
+            .parse_standalone_expression()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn prints_only_required_parentheses() {
+        let cases = vec![
+            ("2 + 3 * 4", "2 + 3 * 4"),
+            ("(2 + 3) * 4", "(2 + 3) * 4"),
+            ("(2 * 3) + 4", "2 * 3 + 4"),
+            ("10 - (2 - 3)", "10 - (2 - 3)"),
+            ("(10 - 2) - 3", "10 - 2 - 3"),
+            ("(a || b) && c", "(a || b) && c"),
+            ("a || (b && c)", "a || b && c"),
+            ("-(1 + 2)", "-(1 + 2)"),
+            ("-(-x)", "-(-x)"),
+            ("(1 < 2) == true", "1 < 2 == true"),
+        ];
+        for (src, expected) in cases {
+            assert_eq!(round_trip(src), expected, "{}", src);
+            assert_eq!(round_trip(expected), expected);
         }
     }
 }
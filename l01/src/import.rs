@@ -0,0 +1,232 @@
+use crate::diagnostics::Diagnostic;
+use crate::grammar::{FunctionDecl, ImportStatement, Statement};
+use crate::prog::Prog;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/**
+ * 处理程序中的 import 语句
+ * root 为程序所在文件的路径，import 语句中的路径相对于语句所在的文件，
+ * 如 lib/a.ps 中的 import "b.ps"; 导入的是 lib/b.ps。
+ * load 根据这样得到的路径读取并解析被导入的文件。被导入文件中顶层声明的函数会追加到 prog 的末尾，
+ * 之后的引用消解就能像程序自己声明的函数一样消解对它们的调用；被导入文件中的其他顶层语句不会执行。
+ * 被导入的文件可以继续导入其他文件，同一个路径只会导入一次，出现循环导入（包括导入 root 本身）时返回错误。
+ * 错误的位置为 root 中引起错误的 import 语句的位置，出错的 import 语句不在 root 中时，
+ * 错误信息中会注明它所在的文件与位置。
+ * 需要在引用消解之前调用。
+ */
+pub fn resolve_imports(
+    prog: &mut Prog,
+    root: &str,
+    load: &mut dyn FnMut(&str) -> Result<Prog, String>,
+) -> Result<(), Diagnostic> {
+    let root = normalize(Path::new(root));
+    let mut stack = vec![root.to_string()];
+    let mut loaded = vec![root].into_iter().collect();
+    let mut imported = Vec::new();
+    // 每个被导入的函数是由 root 中的哪个 import 语句引入的
+    let mut sources = Vec::new();
+    for x in &prog.stmts {
+        if let Statement::Import(stmt) = x {
+            import(stmt, load, &mut stack, &mut loaded, &mut imported)
+                .map_err(|e| Diagnostic::new(e, stmt.line, stmt.col))?;
+            sources.resize(imported.len(), stmt);
+        }
+    }
+
+    // 导入的函数与顶层的函数处于同一个作用域，不能重名
+    let mut names: HashSet<String> = prog
+        .stmts
+        .iter()
+        .filter_map(|x| match x {
+            Statement::FunctionDecl(decl) => Some(decl.name.to_string()),
+            _ => None,
+        })
+        .collect();
+    for ((path, decl), stmt) in imported.iter().zip(&sources) {
+        if !names.insert(decl.name.to_string()) {
+            return Err(Diagnostic::new(
+                format!(
+                    "function {} imported from {} is already declared",
+                    decl.name, path
+                ),
+                stmt.line,
+                stmt.col,
+            ));
+        }
+    }
+
+    prog.stmts.extend(
+        imported
+            .into_iter()
+            .map(|(_, decl)| Statement::FunctionDecl(decl)),
+    );
+    Ok(())
+}
+
+// 导入 import 语句 stmt 所引用的文件及其导入的文件，把其中的函数声明连同所在的路径收集到 imported 中
+// stack 为正在导入的文件路径，最后一个即为 stmt 所在的文件，用于发现循环导入；loaded 为已经导入过的路径
+fn import(
+    stmt: &ImportStatement,
+    load: &mut dyn FnMut(&str) -> Result<Prog, String>,
+    stack: &mut Vec<String>,
+    loaded: &mut HashSet<String>,
+    imported: &mut Vec<(String, FunctionDecl)>,
+) -> Result<(), String> {
+    let path = resolve_path(stack, &stmt.path);
+    if let Some(i) = stack.iter().position(|x| *x == path) {
+        let cycle = [&stack[i..], &[path]].concat();
+        return Err(format!(
+            "import cycle: {}{}",
+            cycle.join(" -> "),
+            location(stack, stmt)
+        ));
+    }
+    if !loaded.insert(path.to_string()) {
+        return Ok(());
+    }
+
+    let module = load(&path).map_err(|e| {
+        format!(
+            "cannot import {}{}: {}",
+            stmt.path,
+            location(stack, stmt),
+            e
+        )
+    })?;
+    stack.push(path.to_string());
+    for x in &module.stmts {
+        if let Statement::Import(stmt) = x {
+            import(stmt, load, stack, loaded, imported)?;
+        }
+    }
+    stack.pop();
+
+    for x in module.stmts {
+        if let Statement::FunctionDecl(decl) = x {
+            imported.push((path.to_string(), decl));
+        }
+    }
+    Ok(())
+}
+
+// import 语句中的路径相对于语句所在的文件（stack 的最后一个）
+fn resolve_path(stack: &[String], path: &str) -> String {
+    let dir = stack
+        .last()
+        .and_then(|x| Path::new(x).parent())
+        .unwrap_or_else(|| Path::new(""));
+    normalize(&dir.join(path))
+}
+
+// 去掉路径中的 . 并消去 ..，使指向同一个文件的不同写法得到相同的路径
+fn normalize(path: &Path) -> String {
+    let mut result = PathBuf::new();
+    for x in path.components() {
+        match x {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(result.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                result.pop();
+            }
+            x => result.push(x),
+        }
+    }
+    result.to_string_lossy().into_owned()
+}
+
+// 不在 root 中的 import 语句所在的文件与位置，root 中的 import 语句的位置由 Diagnostic 给出
+fn location(stack: &[String], stmt: &ImportStatement) -> String {
+    match stack {
+        [_] => String::new(),
+        _ => format!(
+            " ({}:{}:{})",
+            stack.last().map(|x| x.as_str()).unwrap_or_default(),
+            stmt.line,
+            stmt.col
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::parser::parse_str;
+    use crate::ref_resolver::RefResolver;
+    use std::collections::HashMap;
+
+    // 从内存中的文件导入
+    fn resolve(files: &[(&str, &str)], root: &str) -> Result<Prog, String> {
+        let files: HashMap<&str, &str> = files.iter().copied().collect();
+        let mut prog =
+            parse_str(files[root.trim_start_matches("./")]).map_err(|e| e.to_string())?;
+        resolve_imports(&mut prog, root, &mut |path| match files.get(path) {
+            Some(code) => parse_str(code).map_err(|e| e.to_string()),
+            None => Err(format!("no such file {}", path)),
+        })
+        .map_err(|e| e.to_string())?;
+        Ok(prog)
+    }
+
+    #[test]
+    fn calls_imported_function() {
+        let mut prog = resolve(
+            &[
+                ("main.ps", "import \"greet.ps\"; greet(\"world\");"),
+                (
+                    "greet.ps",
+                    "function greet(x){ println(\"hello \" + x); } greet(\"ignored\");",
+                ),
+            ],
+            "main.ps",
+        )
+        .unwrap();
+        RefResolver::resolve(&mut prog).unwrap();
+        let mut out = Vec::new();
+        Interpreter::new().run_program(&prog, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "hello world\n");
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_importing_file() {
+        let prog = resolve(
+            &[
+                ("app/main.ps", "import \"lib/a.ps\";"),
+                ("app/lib/a.ps", "import \"b.ps\"; function a(){}"),
+                ("app/lib/b.ps", "import \"../util.ps\"; function b(){}"),
+                ("app/util.ps", "function util(){}"),
+                ("b.ps", "function wrong(){}"),
+            ],
+            "./app/main.ps",
+        )
+        .unwrap();
+        assert_eq!(prog.functions(), vec!["util", "b", "a"]);
+    }
+
+    #[test]
+    fn detects_cycle_back_to_root() {
+        let e = resolve(
+            &[
+                ("main.ps", "import \"lib/a.ps\";"),
+                ("lib/a.ps", "\nimport \"../main.ps\";"),
+            ],
+            "main.ps",
+        )
+        .unwrap_err();
+        assert_eq!(
+            e,
+            "import cycle: main.ps -> lib/a.ps -> main.ps (lib/a.ps:2:1) at 1:1"
+        );
+
+        let e = resolve(&[("main.ps", "import \"main.ps\";")], "main.ps").unwrap_err();
+        assert_eq!(e, "import cycle: main.ps -> main.ps at 1:1");
+    }
+
+    #[test]
+    fn reports_missing_file() {
+        let e = resolve(&[("main.ps", "import \"x.ps\";")], "main.ps").unwrap_err();
+        assert_eq!(e, "cannot import x.ps: no such file x.ps at 1:1");
+    }
+}
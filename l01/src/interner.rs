@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/**
+ * 字符串驻留池
+ * 相同的字符串只保存一份，并用一个整数 id 代表它
+ * 驻留的字符串以 Rc<str> 保存，Token 与 AST 中的名称可以直接共享它，而不必各自分配内存
+ */
+#[derive(Default)]
+pub struct StringInterner {
+    ids: HashMap<Rc<str>, u32>,
+    strings: Vec<Rc<str>>, // 下标即为 id
+}
+impl StringInterner {
+    pub fn new() -> StringInterner {
+        StringInterner::default()
+    }
+
+    // 获取字符串的 id，如果尚未驻留则先加入驻留池
+    pub fn intern(&mut self, s: &str) -> u32 {
+        if let Some(&id) = self.ids.get(s) {
+            return id;
+        }
+
+        let id = self.strings.len() as u32;
+        let s: Rc<str> = s.into();
+        self.strings.push(s.clone());
+        self.ids.insert(s, id);
+        id
+    }
+
+    // 驻留字符串，返回驻留池中共享的那一份
+    pub fn symbol(&mut self, s: &str) -> Rc<str> {
+        let id = self.intern(s);
+        self.strings[id as usize].clone()
+    }
+
+    // 获取已驻留字符串的 id
+    pub fn get(&self, s: &str) -> Option<u32> {
+        self.ids.get(s).copied()
+    }
+
+    // 根据 id 取回字符串
+    pub fn resolve(&self, id: u32) -> Option<&str> {
+        self.strings.get(id as usize).map(|x| &**x)
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_counter::count_allocations;
+    use crate::lexer::{tokenize, Tokenizer};
+    use crate::parser::parse_str;
+    use crate::token::Token;
+
+    #[test]
+    fn same_string_same_id() {
+        let mut interner = StringInterner::new();
+        let a = interner.intern("sayHello");
+        let b = interner.intern("println");
+        assert_eq!(interner.intern("sayHello"), a);
+        assert_ne!(a, b);
+        assert_eq!(interner.get("println"), Some(b));
+        assert_eq!(interner.resolve(a), Some("sayHello"));
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn tokens_share_interned_text() {
+        let mut tokenizer = Tokenizer::new("f(); f();");
+        let tokens: Vec<Token> = tokenizer.by_ref().map(|x| x.unwrap()).collect();
+        assert!(Rc::ptr_eq(&tokens[0].text, &tokens[4].text));
+        let id = tokenizer.interner().get("f");
+        assert!(id.is_some());
+        assert_eq!(tokenizer.interner().get("f"), id);
+    }
+
+    // 10000 次调用的程序：重复的名称与符号不再为每个 Token 分配内存
+    // 驻留之前词法分析约需 40000 次分配（每个 Token 一次），解析约需 70000 次
+    #[test]
+    fn interning_reduces_allocations() {
+        let code = "function sayHello(){}\n".to_string() + &"sayHello();\n".repeat(10000);
+        let (tokens, n) = count_allocations(|| tokenize(&code).unwrap());
+        assert_eq!(tokens.len(), 40007);
+        assert!(n < 100, "tokenize: {} allocations", n);
+
+        let (prog, n) = count_allocations(|| parse_str(&code).unwrap());
+        assert_eq!(prog.stmts.len(), 10001);
+        assert!(n < 25000, "parse_str: {} allocations", n);
+    }
+}
@@ -1,36 +1,1058 @@
-pub struct Interpreter {}
-
-use crate::grammar::{FunctionCall, Statement};
+use crate::builtins::{self, Clock, Host};
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+use crate::grammar::{
+    Expression, ForStatement, FunctionCall, FunctionDecl, Statement, WhileStatement,
+};
+use crate::lexer::{tokenize, TokenBuffer};
+use crate::parser::Parser;
 use crate::prog::Prog;
+use crate::value::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::time::Instant;
+
+// 默认的最大调用深度
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+// 以默认的最大调用深度执行程序所需的栈大小
+// 解释器递归地执行函数调用，每层调用要经过 execute、evaluate、run_call 等多个函数，
+// 调试构建中每层调用占用的栈可达 140KB（实测），远超主线程默认的 8MB，
+// 因此宿主程序应在栈至少这么大的线程上执行解释器，使调用过深时报错而不是栈溢出
+pub const STACK_SIZE: usize = 256 << 20;
+
+pub struct Interpreter {
+    max_call_depth: usize, // 函数调用的最大嵌套深度，超过后报错而不是让宿主栈溢出
+    use_entry_point: bool, // 为 true 且程序声明了 main 时，只执行 main()
+    max_steps: Option<usize>, // 一次执行最多执行的步数（语句数与循环次数之和），None 表示不限制
+    on_statement: Option<Box<StatementHook>>, // 每个语句执行之前调用
+    host_functions: HashMap<String, Box<HostFunction>>, // 宿主程序提供的函数，按名称查找
+    clock: Box<Clock>,     // 内置函数 time 使用的时钟
+}
+
+// 执行每个语句之前调用的钩子，可以查看或修改当前的变量，供调试器使用
+pub type StatementHook = dyn Fn(&Statement, &mut Environment);
+
+// 宿主程序提供的函数，参数为实参的值，返回函数的值或错误信息
+pub type HostFunction = dyn Fn(&[Value]) -> Result<Value, String>;
+
+// 一次执行过程中共享的状态
+struct Context<'a> {
+    prog: &'a Prog,
+    out: &'a mut dyn Write,   // 程序的输出，println / print 写入这里
+    err: &'a mut dyn Write,   // 程序的错误输出，eprintln 写入这里
+    env: &'a mut Environment, // 变量
+    returning: Option<Value>, // 执行了 return 语句，正在从函数返回，值为返回值
+    steps: usize,             // 已经执行的步数
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
 
 impl Interpreter {
-    pub fn run(prog: &Prog) -> Result<(), String> {
+    pub fn new() -> Interpreter {
+        Interpreter::with_max_call_depth(DEFAULT_MAX_CALL_DEPTH)
+    }
+
+    pub fn with_max_call_depth(max_call_depth: usize) -> Interpreter {
+        // 默认的时钟以创建解释器的时刻为起点
+        let epoch = Instant::now();
+        Interpreter {
+            max_call_depth,
+            use_entry_point: false,
+            max_steps: None,
+            on_statement: None,
+            host_functions: HashMap::new(),
+            clock: Box::new(move || epoch.elapsed().as_millis() as i64),
+        }
+    }
+
+    // 设置一次执行最多执行的步数，每执行一个语句或进行一次循环条件的判断计为一步
+    // 超过后报错而不是一直执行下去，用于在嵌入的环境中防止死循环
+    pub fn with_instruction_budget(mut self, max_steps: usize) -> Interpreter {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    // 设置执行每个语句之前调用的钩子
+    pub fn on_statement(
+        mut self,
+        hook: impl Fn(&Statement, &mut Environment) + 'static,
+    ) -> Interpreter {
+        self.on_statement = Some(Box::new(hook));
+        self
+    }
+
+    // 添加宿主程序提供的函数，同名的函数会被覆盖
+    // 调用没有在程序中声明的函数时，先查找宿主函数，再查找内置函数，因此宿主函数可以替换内置函数
+    // 引用消解时需要用 RefResolver::resolve_with 把这些名称（见 host_function_names）当作合法的函数
+    pub fn with_host_functions(
+        mut self,
+        functions: HashMap<String, Box<HostFunction>>,
+    ) -> Interpreter {
+        self.host_functions.extend(functions);
+        self
+    }
+
+    // 设置内置函数 time 使用的时钟，时钟返回从某个固定时刻起经过的毫秒数
+    // 可以传入固定的时钟，使用到 time 的程序每次执行的结果都相同
+    pub fn with_clock(mut self, clock: impl Fn() -> i64 + 'static) -> Interpreter {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    // 所有宿主函数的名称
+    pub fn host_function_names(&self) -> HashSet<String> {
+        self.host_functions.keys().cloned().collect()
+    }
+
+    // 设置是否以 main 作为程序的入口
+    pub fn use_entry_point(mut self, enabled: bool) -> Interpreter {
+        self.use_entry_point = enabled;
+        self
+    }
+
+    // 执行程序
+    // 启用了入口函数且程序声明了 main 时，只执行 main()，忽略顶层的其他语句；
+    // 否则依次执行所有顶层语句
+    pub fn run(&self, prog: &Prog) -> Result<(), String> {
+        self.run_with_writer(prog, &mut io::stdout())
+    }
+
+    // 与 run 相同，但程序的输出写入 out 而不是标准输出
+    pub fn run_with_writer(&self, prog: &Prog, out: &mut dyn Write) -> Result<(), String> {
+        self.run_with_writers(prog, out, &mut io::stderr())
+    }
+
+    // 与 run 相同，但程序的输出写入 out，错误输出写入 err
+    pub fn run_with_writers(
+        &self,
+        prog: &Prog,
+        out: &mut dyn Write,
+        err: &mut dyn Write,
+    ) -> Result<(), String> {
+        self.run_program_with_writers(prog, out, err)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    // 执行程序，程序的输出写入 out，供嵌入解释器的宿主程序使用
+    // 返回最后一个执行的顶层语句的值（函数声明不算执行），以 main 为入口时返回 main 的返回值
+    pub fn run_program(&self, prog: &Prog, out: &mut dyn Write) -> Result<Value, RuntimeError> {
+        self.run_program_with_writers(prog, out, &mut io::stderr())
+    }
+
+    // 与 run_program 相同，但程序的错误输出写入 err 而不是标准错误
+    pub fn run_program_with_writers(
+        &self,
+        prog: &Prog,
+        out: &mut dyn Write,
+        err: &mut dyn Write,
+    ) -> Result<Value, RuntimeError> {
+        let ctx = &mut Context {
+            prog,
+            out,
+            err,
+            env: &mut Environment::new(),
+            returning: None,
+            steps: 0,
+        };
+        if self.use_entry_point {
+            if let Some(decl) = prog.entry_point().and_then(|x| prog.function_decl(&[x])) {
+                return self.run_function(ctx, decl, Vec::new(), 0);
+            }
+        }
+
+        let mut value = Value::Null;
         for x in &prog.stmts {
-            if let Statement::FunctionCall(call) = x {
-                Interpreter::run_call(call)?
+            let result = self.execute(ctx, x, 0)?;
+            if !matches!(x, Statement::FunctionDecl(_) | Statement::Empty(_)) {
+                value = result;
             }
         }
 
-        Ok(())
+        Ok(value)
+    }
+
+    // 执行 prog 中的一个顶层语句，返回语句的值，声明的变量保存在 env 中
+    // 函数声明本身不执行，其值为 Value::Null
+    pub fn run_statement(
+        &self,
+        prog: &Prog,
+        stmt: &Statement,
+        env: &mut Environment,
+    ) -> Result<Value, String> {
+        let ctx = &mut Context {
+            prog,
+            out: &mut io::stdout(),
+            err: &mut io::stderr(),
+            env,
+            returning: None,
+            steps: 0,
+        };
+        self.execute(ctx, stmt, 0).map_err(|e| e.to_string())
+    }
+
+    // 解析并在 env 中计算一个单独的表达式，如 "x + 1"，供需要求值配置等的宿主程序使用
+    // 表达式之后不能有其他内容；表达式中的赋值只作用于 env 的一份拷贝，不会修改 env；只能调用内置函数与宿主函数
+    pub fn eval_expression(&self, src: &str, env: &Environment) -> Result<Value, String> {
+        let tokens = tokenize(src).map_err(|e| e.to_string())?;
+        let expr = Parser::new(TokenBuffer::new(tokens.into_iter()))
+            .parse_standalone_expression()
+            .map_err(|e| e.to_string())?;
+        let ctx = &mut Context {
+            prog: &Prog::new(Vec::new()),
+            out: &mut io::stdout(),
+            err: &mut io::stderr(),
+            env: &mut env.clone(),
+            returning: None,
+            steps: 0,
+        };
+        self.evaluate(ctx, &expr, 0).map_err(|e| e.to_string())
     }
 
-    fn run_call(call: &FunctionCall) -> Result<(), String> {
-        match call.definition {
-            None => {
-                if call.name == "println" {
-                    println!("{}", call.parameters.join(" "));
-                    Ok(())
-                } else {
-                    Err(format!("Unknown function {}", call.name))
+    // 执行一个语句，depth 为所处的调用深度
+    fn execute(
+        &self,
+        ctx: &mut Context,
+        stmt: &Statement,
+        depth: usize,
+    ) -> Result<Value, RuntimeError> {
+        self.step(ctx)?;
+        if let Some(hook) = &self.on_statement {
+            hook(stmt, ctx.env);
+        }
+
+        match stmt {
+            Statement::FunctionDecl(_) => Ok(Value::Null),
+            Statement::FunctionCall(call) => self.run_call(ctx, call, depth),
+            Statement::VariableDecl(decl) => {
+                let value = match &decl.init {
+                    Some(x) => self.evaluate(ctx, x, depth)?,
+                    None => Value::Null,
+                };
+                ctx.env.define(&decl.name, value);
+                Ok(Value::Null)
+            }
+            Statement::Return(stmt) => {
+                let value = match &stmt.value {
+                    Some(x) => self.evaluate(ctx, x, depth)?,
+                    None => Value::Null,
+                };
+                ctx.returning = Some(value);
+                Ok(Value::Null)
+            }
+            Statement::While(stmt) => self.run_while(ctx, stmt, depth),
+            Statement::For(stmt) => self.run_for(ctx, stmt, depth),
+            // 被导入的函数在执行之前已经由 resolve_imports 合并到程序中
+            Statement::Import(_) => Ok(Value::Null),
+            Statement::Empty(_) => Ok(Value::Null),
+            Statement::Assignment(stmt) => {
+                let value = self.evaluate(ctx, &stmt.value, depth)?;
+                if !ctx.env.assign(&stmt.name, value) {
+                    return Err(RuntimeError::at(
+                        format!("Unknown variable {}", stmt.name),
+                        stmt.line,
+                        stmt.col,
+                    ));
                 }
+                Ok(Value::Null)
             }
-            Some(def) => {
-                for x in &{ unsafe { def.as_ref() } }.body.stmts {
-                    Interpreter::run_call(x)?
+            Statement::ExpressionStatement(expr, _) => self.evaluate(ctx, expr, depth),
+        }
+    }
+
+    // depth 为当前调用所处的嵌套深度，顶层调用为 0
+    // 自定义函数的值为 return 语句的值，没有执行 return 时为 Value::Null
+    fn run_call(
+        &self,
+        ctx: &mut Context,
+        call: &FunctionCall,
+        depth: usize,
+    ) -> Result<Value, RuntimeError> {
+        let args = call
+            .parameters
+            .iter()
+            .map(|x| self.evaluate(ctx, x, depth))
+            .collect::<Result<Vec<Value>, RuntimeError>>()?;
+
+        // 本次调用产生的错误带上调用所在的位置，函数体中产生的错误已经带有各自的位置
+        let at = |e: String| RuntimeError::at(e, call.line, call.col);
+        match &call.definition {
+            None => match (
+                self.host_functions.get(&*call.name),
+                builtins::get(&call.name),
+            ) {
+                (Some(host), _) => host(&args).map_err(at),
+                (None, Some(builtin)) => {
+                    let host = &mut Host {
+                        out: ctx.out,
+                        err: ctx.err,
+                        clock: self.clock.as_ref(),
+                    };
+                    builtin(host, &args).map_err(at)
+                }
+                (None, None) => Err(at(format!("Unknown function {}", call.name))),
+            },
+            Some(path) => {
+                if depth >= self.max_call_depth {
+                    return Err(at("stack overflow: max call depth exceeded".to_string()));
+                }
+
+                let decl = ctx
+                    .prog
+                    .function_decl(path)
+                    .ok_or_else(|| at(format!("Invalid definition of function {}", call.name)))?;
+                if args.len() != decl.params.len() {
+                    return Err(at(format!(
+                        "function {} expects {} argument(s), got {}",
+                        decl.name,
+                        decl.params.len(),
+                        args.len()
+                    )));
+                }
+                self.run_function(ctx, decl, args, depth)
+            }
+        }
+    }
+
+    // 执行自定义函数的函数体，args 为实参的值，depth 为调用所处的嵌套深度
+    // 参数作为函数的局部变量，缺少的参数为 null
+    fn run_function(
+        &self,
+        ctx: &mut Context,
+        decl: &FunctionDecl,
+        args: Vec<Value>,
+        depth: usize,
+    ) -> Result<Value, RuntimeError> {
+        // 空的函数体不需要建立栈桢
+        if decl.body.is_empty() {
+            return Ok(Value::Null);
+        }
+        ctx.env.push_frame();
+        let mut args = args.into_iter();
+        for x in &decl.params {
+            ctx.env.define(x, args.next().unwrap_or(Value::Null));
+        }
+        let mut result = Ok(());
+        for x in &decl.body.stmts {
+            result = self.execute(ctx, x, depth + 1).map(|_| ());
+            if result.is_err() || ctx.returning.is_some() {
+                break;
+            }
+        }
+        ctx.env.pop_frame();
+
+        result?;
+        Ok(ctx.returning.take().unwrap_or(Value::Null))
+    }
+
+    // 执行 while 循环，每次循环的循环体都在新的作用域中执行
+    fn run_while(
+        &self,
+        ctx: &mut Context,
+        stmt: &WhileStatement,
+        depth: usize,
+    ) -> Result<Value, RuntimeError> {
+        loop {
+            self.step(ctx)?;
+            if !self.evaluate_bool(ctx, &stmt.condition, depth, "while")? {
+                return Ok(Value::Null);
+            }
+
+            self.run_block(ctx, &stmt.body.stmts, depth)?;
+            if ctx.returning.is_some() {
+                return Ok(Value::Null);
+            }
+        }
+    }
+
+    // 执行 for 循环，init 中声明的变量位于整个循环的作用域中，每次循环的循环体在新的作用域中执行
+    fn run_for(
+        &self,
+        ctx: &mut Context,
+        stmt: &ForStatement,
+        depth: usize,
+    ) -> Result<Value, RuntimeError> {
+        ctx.env.push_scope();
+        let result = self.run_for_in_scope(ctx, stmt, depth);
+        ctx.env.pop_scope();
+        result
+    }
+
+    fn run_for_in_scope(
+        &self,
+        ctx: &mut Context,
+        stmt: &ForStatement,
+        depth: usize,
+    ) -> Result<Value, RuntimeError> {
+        if let Some(x) = &stmt.init {
+            self.execute(ctx, x, depth)?;
+        }
+
+        loop {
+            self.step(ctx)?;
+            if let Some(x) = &stmt.condition {
+                if !self.evaluate_bool(ctx, x, depth, "for")? {
+                    return Ok(Value::Null);
                 }
+            }
+
+            self.run_block(ctx, &stmt.body.stmts, depth)?;
+            if ctx.returning.is_some() {
+                return Ok(Value::Null);
+            }
+
+            if let Some(x) = &stmt.update {
+                self.execute(ctx, x, depth)?;
+            }
+        }
+    }
 
-                Ok(())
+    // 在新的作用域中执行语句块，执行了 return 时提前结束
+    fn run_block(
+        &self,
+        ctx: &mut Context,
+        stmts: &[Statement],
+        depth: usize,
+    ) -> Result<(), RuntimeError> {
+        ctx.env.push_scope();
+        let mut result = Ok(());
+        for x in stmts {
+            result = self.execute(ctx, x, depth).map(|_| ());
+            if result.is_err() || ctx.returning.is_some() {
+                break;
             }
         }
+        ctx.env.pop_scope();
+        result
+    }
+
+    // 计入一步，超过限制时报错
+    fn step(&self, ctx: &mut Context) -> Result<(), RuntimeError> {
+        ctx.steps += 1;
+        match self.max_steps {
+            Some(max) if ctx.steps > max => Err(RuntimeError::new(format!(
+                "instruction budget exceeded: more than {} steps executed",
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    // 计算表达式的值
+    fn evaluate(
+        &self,
+        ctx: &mut Context,
+        expr: &Expression,
+        depth: usize,
+    ) -> Result<Value, RuntimeError> {
+        match expr {
+            Expression::StringLiteral(x) => Ok(Value::Str(x.to_string())),
+            Expression::IntegerLiteral(x) => Ok(Value::Int(*x)),
+            Expression::Char(x) => Ok(Value::Char(*x)),
+            Expression::Bool(x) => Ok(Value::Bool(*x)),
+            Expression::Variable(x) => ctx
+                .env
+                .get(x)
+                .cloned()
+                .ok_or_else(|| RuntimeError::new(format!("Unknown variable {}", x))),
+            Expression::Call(call) => self.run_call(ctx, call, depth),
+            Expression::Unary { op, operand } if op == "!" => {
+                let operand = self.evaluate_bool(ctx, operand, depth, op)?;
+                Ok(Value::Bool(!operand))
+            }
+            Expression::Unary { op, operand } => {
+                let operand = self.evaluate(ctx, operand, depth)?;
+                Ok(Interpreter::unary(op, operand)?)
+            }
+            Expression::Binary { op, left, right } => {
+                let left = self.evaluate(ctx, left, depth)?;
+                let right = self.evaluate(ctx, right, depth)?;
+                Ok(Interpreter::binary(op, left, right)?)
+            }
+            Expression::Assign {
+                name,
+                value,
+                line,
+                col,
+            } => {
+                // 赋值表达式的值为赋给变量的值
+                let value = self.evaluate(ctx, value, depth)?;
+                if !ctx.env.assign(name, value.clone()) {
+                    return Err(RuntimeError::at(
+                        format!("Unknown variable {}", name),
+                        *line,
+                        *col,
+                    ));
+                }
+                Ok(value)
+            }
+            Expression::Logical { op, left, right } => {
+                // 短路求值：&& 左侧为 false、|| 左侧为 true 时不再计算右侧
+                let left = self.evaluate_bool(ctx, left, depth, op)?;
+                if (op == "&&" && !left) || (op == "||" && left) {
+                    return Ok(Value::Bool(left));
+                }
+                let right = self.evaluate_bool(ctx, right, depth, op)?;
+                Ok(Value::Bool(right))
+            }
+            Expression::Member {
+                target, line, col, ..
+            } => {
+                // 语法上已经支持成员访问，但还没有可以访问成员的值
+                self.evaluate(ctx, target, depth)?;
+                Err(RuntimeError::at(
+                    "member access not yet supported".to_string(),
+                    *line,
+                    *col,
+                ))
+            }
+            Expression::Index {
+                target,
+                index,
+                line,
+                col,
+            } => {
+                self.evaluate(ctx, target, depth)?;
+                self.evaluate(ctx, index, depth)?;
+                Err(RuntimeError::at(
+                    "indexing not yet supported".to_string(),
+                    *line,
+                    *col,
+                ))
+            }
+        }
+    }
+
+    // 计算一元运算（! 以外），目前只有取负
+    pub(crate) fn unary(op: &str, operand: Value) -> Result<Value, String> {
+        match (op, operand) {
+            ("-", Value::Int(x)) => x
+                .checked_neg()
+                .map(Value::Int)
+                .ok_or_else(|| format!("Integer overflow: -{}", x)),
+            (op, x) => Err(format!("Type error: unsupported operand {}{}", op, x)),
+        }
+    }
+
+    // 计算二元运算
+    // + 的规则：
+    // - Int + Int 为整数加法
+    // - Str + Str 为字符串连接
+    // - Str 与 Int 相加时，Int 先转换为十进制字符串再连接
+    // - - * / % 只支持 Int 与 Int，除数为 0 时报错
+    // 整数运算的结果超出 i64 的范围时报错，而不是回绕
+    // 比较运算的结果为 Bool：
+    // - == 与 != 可用于任意两个值，类型不同的值总是不相等
+    // - < <= > >= 只能用于同类型的 Int、Char 或 Str，字符串按字典序比较
+    // 其余组合均为类型错误
+    pub(crate) fn binary(op: &str, left: Value, right: Value) -> Result<Value, String> {
+        match (op, left, right) {
+            ("==", l, r) => Ok(Value::Bool(l == r)),
+            ("!=", l, r) => Ok(Value::Bool(l != r)),
+            ("<" | "<=" | ">" | ">=", Value::Int(l), Value::Int(r)) => {
+                Ok(Value::Bool(Interpreter::compare(op, l.cmp(&r))))
+            }
+            ("<" | "<=" | ">" | ">=", Value::Char(l), Value::Char(r)) => {
+                Ok(Value::Bool(Interpreter::compare(op, l.cmp(&r))))
+            }
+            ("<" | "<=" | ">" | ">=", Value::Str(l), Value::Str(r)) => {
+                Ok(Value::Bool(Interpreter::compare(op, l.cmp(&r))))
+            }
+            ("/" | "%", Value::Int(_), Value::Int(0)) => Err("Division by zero".to_string()),
+            ("+" | "-" | "*" | "/" | "%", Value::Int(l), Value::Int(r)) => {
+                let result = match op {
+                    "+" => l.checked_add(r),
+                    "-" => l.checked_sub(r),
+                    "*" => l.checked_mul(r),
+                    "/" => l.checked_div(r),
+                    _ => l.checked_rem(r),
+                };
+                result
+                    .map(Value::Int)
+                    .ok_or_else(|| format!("Integer overflow: {} {} {}", l, op, r))
+            }
+            ("+", Value::Str(l), Value::Str(r)) => Ok(Value::Str(l + &r)),
+            ("+", Value::Str(l), Value::Int(r)) => Ok(Value::Str(format!("{}{}", l, r))),
+            ("+", Value::Int(l), Value::Str(r)) => Ok(Value::Str(format!("{}{}", l, r))),
+            (op, l, r) => Err(format!(
+                "Type error: unsupported operands {} {} {}",
+                l, op, r
+            )),
+        }
+    }
+
+    // 根据两个值的大小关系计算比较运算的结果
+    fn compare(op: &str, ordering: Ordering) -> bool {
+        match op {
+            "<" => ordering == Ordering::Less,
+            "<=" => ordering != Ordering::Greater,
+            ">" => ordering == Ordering::Greater,
+            _ => ordering != Ordering::Less, // >=
+        }
+    }
+
+    // 计算一个必须为布尔值的运算数，op 用于生成错误信息
+    fn evaluate_bool(
+        &self,
+        ctx: &mut Context,
+        expr: &Expression,
+        depth: usize,
+        op: &str,
+    ) -> Result<bool, RuntimeError> {
+        match self.evaluate(ctx, expr, depth)? {
+            Value::Bool(x) => Ok(x),
+            x => Err(RuntimeError::new(format!(
+                "Type error: operand of {} must be bool, got {}",
+                op, x
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_str;
+    use crate::ref_resolver::RefResolver;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::thread;
+
+    // 解析并执行程序，返回程序的输出与执行结果
+    fn run(interpreter: &Interpreter, code: &str) -> (String, Result<Value, RuntimeError>) {
+        let mut prog = parse_str(code).unwrap();
+        RefResolver::resolve(&mut prog).unwrap();
+        let mut out = Vec::new();
+        let result = interpreter.run_program(&prog, &mut out);
+        (String::from_utf8(out).unwrap(), result)
+    }
+
+    // 在足够大的栈上执行，调用深度达到上限之前不会栈溢出
+    fn run_on_large_stack(code: &'static str) -> Result<Value, RuntimeError> {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || run(&Interpreter::new(), code).1)
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn unbounded_recursion_is_an_error() {
+        let e = run_on_large_stack("function f(){ f(); } f();").unwrap_err();
+        assert_eq!(e.message, "stack overflow: max call depth exceeded");
+        assert_eq!(e.position, Some((1, 15)));
+
+        let e = run_on_large_stack(
+            "function f(n){ return g(n) + 1; } function g(n){ return f(n) + 1; } println(f(0));",
+        )
+        .unwrap_err();
+        assert_eq!(e.message, "stack overflow: max call depth exceeded");
+    }
+
+    #[test]
+    fn max_call_depth_is_configurable() {
+        let code = "function f(n){ while(n > 0){ return f(n - 1) + 1; } return 0; } f(5);";
+        let (_, result) = run(&Interpreter::with_max_call_depth(6), code);
+        assert_eq!(result, Ok(Value::Int(5)));
+        let (_, result) = run(&Interpreter::with_max_call_depth(5), code);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn eval_expression_from_source() {
+        let interpreter = Interpreter::new();
+        let mut env = Environment::new();
+        assert_eq!(
+            interpreter.eval_expression("1 + 2 * 3", &env),
+            Ok(Value::Int(7))
+        );
+        env.define("x", Value::Int(10));
+        assert_eq!(
+            interpreter.eval_expression("x + 1", &env),
+            Ok(Value::Int(11))
+        );
+
+        // 赋值不会修改 env
+        assert_eq!(
+            interpreter.eval_expression("x = 5", &env),
+            Ok(Value::Int(5))
+        );
+        assert_eq!(env.get("x"), Some(&Value::Int(10)));
+    }
+
+    #[test]
+    fn eval_expression_rejects_trailing_tokens() {
+        let e = Interpreter::new()
+            .eval_expression("1 + 2; 3", &Environment::new())
+            .unwrap_err();
+        assert_eq!(e, "expect end of expression but got Seperator ';' at 1:6");
+    }
+
+    #[test]
+    fn print_has_no_trailing_newline() {
+        let (out, result) = run(
+            &Interpreter::new(),
+            "print(\"a\"); print(\"b\"); print(1, 2);",
+        );
+        assert!(result.is_ok());
+        assert_eq!(out, "ab1 2");
+    }
+
+    #[test]
+    fn boolean_literals() {
+        let code = "let b = true; while (b) { println(\"once\"); b = false; } let truely = 1; println(b, truely);";
+        let (out, result) = run(&Interpreter::new(), code);
+        assert!(result.is_ok());
+        assert_eq!(out, "once\nfalse 1\n");
+        assert_eq!(
+            Interpreter::new().eval_expression("true", &Environment::new()),
+            Ok(Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        let code = "function x(){ println(\"x called\"); return true; }\nprintln(false && x(), true || x(), !false, true && x());";
+        let (out, result) = run(&Interpreter::new(), code);
+        assert!(result.is_ok());
+        assert_eq!(out, "x called\nfalse true true true\n");
+
+        let (_, result) = run(&Interpreter::new(), "println(1 && true);");
+        assert!(result.unwrap_err().message.starts_with("Type error"));
+    }
+
+    #[test]
+    fn string_concatenation() {
+        let (out, result) = run(
+            &Interpreter::new(),
+            "println(\"a\" + \"b\"); println(\"n = \" + 1); println(2 + \"!\");",
+        );
+        assert!(result.is_ok());
+        assert_eq!(out, "ab\nn = 1\n2!\n");
+    }
+
+    #[test]
+    fn assert_builtin() {
+        let (_, result) = run(&Interpreter::new(), "assert(true); assert(1 < 2);");
+        assert!(result.is_ok());
+        let (_, result) = run(&Interpreter::new(), "assert(false);");
+        assert_eq!(result.unwrap_err().message, "assertion failed");
+        let (_, result) = run(&Interpreter::new(), "assert(1);");
+        assert_eq!(
+            result.unwrap_err().message,
+            "Type error: assert expects a bool, got 1"
+        );
+    }
+
+    #[test]
+    fn len_counts_chars() {
+        let (out, result) = run(
+            &Interpreter::new(),
+            "println(len(\"hello\"), len(\"héllo\"), len(\"\"));",
+        );
+        assert!(result.is_ok());
+        assert_eq!(out, "5 5 0\n");
+        let (_, result) = run(&Interpreter::new(), "len(5);");
+        assert_eq!(
+            result.unwrap_err().message,
+            "Type error: len expects a string, got 5"
+        );
+    }
+
+    #[test]
+    fn entry_point_runs_only_main() {
+        let code = "function main(){ println(\"main\"); } println(\"top\");";
+        let (out, _) = run(&Interpreter::new().use_entry_point(true), code);
+        assert_eq!(out, "main\n");
+
+        // 没有启用入口函数时，按顺序执行顶层语句
+        let (out, _) = run(&Interpreter::new(), code);
+        assert_eq!(out, "top\n");
+    }
+
+    #[test]
+    fn entry_point_falls_back_without_main() {
+        let code = "function f(){ println(\"f\"); } f(); println(\"top\");";
+        let (out, result) = run(&Interpreter::new().use_entry_point(true), code);
+        assert_eq!(out, "f\ntop\n");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn unary_minus() {
+        let (out, _) = run(&Interpreter::new(), "println(-5); println(3 - -2);");
+        assert_eq!(out, "-5\n5\n");
+        let env = Environment::new();
+        assert_eq!(
+            Interpreter::new().eval_expression("-5", &env),
+            Ok(Value::Int(-5))
+        );
+        // -- 仍然是一个运算符，在表达式中报错
+        assert_eq!(
+            Interpreter::new().eval_expression("--5", &env),
+            Err("unsupported operator Operator '--' at 1:1, use - (-x) instead".to_string())
+        );
+    }
+
+    #[test]
+    fn comparisons_produce_booleans() {
+        let interpreter = Interpreter::new();
+        let env = Environment::new();
+        assert_eq!(
+            interpreter.eval_expression("1 < 2", &env),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            interpreter.eval_expression("\"a\" == \"a\"", &env),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            interpreter.eval_expression("1 == \"a\"", &env),
+            Ok(Value::Bool(false))
+        );
+        assert_eq!(
+            interpreter.eval_expression("1 != \"a\"", &env),
+            Ok(Value::Bool(true))
+        );
+        // 类型不同时只能比较是否相等
+        assert_eq!(
+            interpreter.eval_expression("1 < \"a\"", &env),
+            Err("Type error: unsupported operands 1 < a".to_string())
+        );
+    }
+
+    #[test]
+    fn parentheses_group_expressions() {
+        let interpreter = Interpreter::new();
+        let env = Environment::new();
+        assert_eq!(
+            interpreter.eval_expression("(2 + 3) * 4", &env),
+            Ok(Value::Int(20))
+        );
+        assert_eq!(
+            interpreter.eval_expression("2 + 3 * 4", &env),
+            Ok(Value::Int(14))
+        );
+        assert_eq!(
+            interpreter.eval_expression("()", &env),
+            Err("expect expression inside parentheses but got Seperator ')' at 1:2".to_string())
+        );
+    }
+
+    #[test]
+    fn binary_operator_precedence() {
+        let interpreter = Interpreter::new();
+        let env = Environment::new();
+        assert_eq!(
+            interpreter.eval_expression("2 + 3 * 4", &env),
+            Ok(Value::Int(14))
+        );
+        assert_eq!(
+            interpreter.eval_expression("1 < 2 && 3 < 4", &env),
+            Ok(Value::Bool(true))
+        );
+        // 左结合
+        assert_eq!(
+            interpreter.eval_expression("10 - 2 - 3", &env),
+            Ok(Value::Int(5))
+        );
+    }
+
+    #[test]
+    fn char_literals() {
+        let interpreter = Interpreter::new();
+        let env = Environment::new();
+        assert_eq!(
+            interpreter.eval_expression("'a'", &env),
+            Ok(Value::Char('a'))
+        );
+        assert_eq!(
+            interpreter.eval_expression("'\\n'", &env),
+            Ok(Value::Char('\n'))
+        );
+        assert_eq!(
+            interpreter.eval_expression("''", &env),
+            Err("Empty char literal at 1:1".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_function_reports_position() {
+        // 未经引用消解的程序在执行时才发现未定义的函数
+        let prog = parse_str("println(1);\n\n  foo();").unwrap();
+        let e = Interpreter::new()
+            .run_program(&prog, &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(e.message, "Unknown function foo");
+        assert_eq!(e.position, Some((3, 3)));
+        assert_eq!(e.to_string(), "Unknown function foo at 3:3");
+    }
+
+    #[test]
+    fn snapshots_taken_before_each_statement() {
+        let snapshots = Rc::new(RefCell::new(Vec::new()));
+        let recorded = Rc::clone(&snapshots);
+        let interpreter = Interpreter::new()
+            .on_statement(move |_, env| recorded.borrow_mut().push(env.snapshot()));
+        let (_, result) = run(&interpreter, "let a = 1;\nlet b = 2;");
+        assert!(result.is_ok());
+
+        let snapshots = snapshots.borrow();
+        assert_eq!(snapshots.len(), 2);
+        assert!(snapshots[0].is_empty());
+        // let b 执行之前只有 a
+        assert_eq!(snapshots[1].get("a"), Some(&Value::Int(1)));
+        assert_eq!(snapshots[1].get("b"), None);
+    }
+
+    #[test]
+    fn restore_discards_later_bindings() {
+        let prog = parse_str("let x = 1;").unwrap();
+        let mut env = Environment::new();
+        let before = env.snapshot();
+        Interpreter::new()
+            .run_statement(&prog, &prog.stmts[0], &mut env)
+            .unwrap();
+        let after = env.snapshot();
+        assert_eq!(before.get("x"), None);
+        assert_eq!(after.get("x"), Some(&Value::Int(1)));
+
+        env.restore(before);
+        assert_eq!(env.get("x"), None);
+    }
+
+    #[test]
+    fn program_result_is_the_last_statement() {
+        let (_, result) = run(&Interpreter::new(), "println(1);\n42;");
+        assert_eq!(result, Ok(Value::Int(42)));
+        // 函数声明不改变程序的结果
+        let (_, result) = run(&Interpreter::new(), "42; function f(){}");
+        assert_eq!(result, Ok(Value::Int(42)));
+        let (_, result) = run(&Interpreter::new(), "");
+        assert_eq!(result, Ok(Value::Null));
+    }
+
+    #[test]
+    fn nested_calls_as_arguments() {
+        let (out, result) = run(
+            &Interpreter::new(),
+            "function greet(name){ return \"hello \" + name; }\nprintln(greet(\"world\"));",
+        );
+        assert_eq!(out, "hello world\n");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn infinite_loop_exhausts_the_budget() {
+        let interpreter = Interpreter::new().with_instruction_budget(1000);
+        let (_, result) = run(&interpreter, "while (true) {}");
+        assert_eq!(
+            result.unwrap_err().message,
+            "instruction budget exceeded: more than 1000 steps executed"
+        );
+    }
+
+    #[test]
+    fn for_loop_counts() {
+        let (out, result) = run(
+            &Interpreter::new(),
+            "for (let i = 0; i < 3; i = i + 1) { print(i); print(\" \"); }",
+        );
+        assert_eq!(out, "0 1 2 ");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn assignment_updates_existing_binding() {
+        let (out, _) = run(&Interpreter::new(), "let x = 1; x = 2; println(x);");
+        assert_eq!(out, "2\n");
+        // 赋值表达式的值是赋给变量的值
+        let (out, _) = run(
+            &Interpreter::new(),
+            "let x = 1; println(x = 3); println(x);",
+        );
+        assert_eq!(out, "3\n3\n");
+
+        let (_, result) = run(&Interpreter::new(), "y = 2;");
+        assert_eq!(result.unwrap_err().to_string(), "Unknown variable y at 1:1");
+    }
+
+    #[test]
+    fn compound_assignment() {
+        let code = "let a = 10; a += 3; let b = 10; b -= 3; let c = 10; c *= 3;
+            let d = 10; d /= 3; let e = 10; e %= 3; println(a, b, c, d, e);";
+        let (out, _) = run(&Interpreter::new(), code);
+        assert_eq!(out, "13 7 30 3 1\n");
+
+        let (_, result) = run(&Interpreter::new(), "z += 1;");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn eprintln_writes_to_the_error_sink() {
+        let prog = parse_str("println(\"out\"); eprintln(\"err\"); print(1);").unwrap();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        Interpreter::new()
+            .run_program_with_writers(&prog, &mut out, &mut err)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "out\n1");
+        assert_eq!(String::from_utf8(err).unwrap(), "err\n");
+    }
+
+    #[test]
+    fn host_functions_are_called_from_programs() {
+        let mut functions: HashMap<String, Box<HostFunction>> = HashMap::new();
+        functions.insert(
+            "answer".to_string(),
+            Box::new(|args: &[Value]| Ok(Value::Int(40 + args.len() as i64))),
+        );
+        let interpreter = Interpreter::new().with_host_functions(functions);
+
+        let mut prog = parse_str("println(answer(1, 2));").unwrap();
+        RefResolver::resolve_with(&mut prog, &interpreter.host_function_names()).unwrap();
+        let mut out = Vec::new();
+        interpreter.run_program(&prog, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "42\n");
+    }
+
+    #[test]
+    fn time_uses_the_injected_clock() {
+        let interpreter = Interpreter::new().with_clock(|| 12345);
+        let (out, _) = run(&interpreter, "println(time()); println(time() - time());");
+        assert_eq!(out, "12345\n0\n");
+    }
+
+    #[test]
+    fn integer_overflow_is_an_error() {
+        let interpreter = Interpreter::new();
+        let env = Environment::new();
+        assert_eq!(
+            interpreter.eval_expression("2000000000 * 2000000000 * 2000000000", &env),
+            Err("Integer overflow: 4000000000000000000 * 2000000000".to_string())
+        );
+        assert_eq!(
+            interpreter.eval_expression("9223372036854775807 + 1", &env),
+            Err("Integer overflow: 9223372036854775807 + 1".to_string())
+        );
+    }
+
+    #[test]
+    fn empty_function_body() {
+        let (out, result) = run(&Interpreter::new(), "function f(){}\nf();");
+        assert_eq!(out, "");
+        assert_eq!(result, Ok(Value::Null));
+
+        let prog = parse_str("function f(){} function g(){ ;; } function h(){ g(); }").unwrap();
+        let empty: Vec<bool> = (0..3)
+            .map(|i| prog.function_decl(&[i]).unwrap().body.is_empty())
+            .collect();
+        assert_eq!(empty, vec![true, true, false]);
     }
 }
@@ -0,0 +1,88 @@
+// 手写的 JSON 输出辅助函数，避免引入 serde 依赖
+
+// 把字符串转义为 JSON 字符串字面量（包含两侧引号）
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// JSON 的值，用于构造需要格式化输出的 JSON
+// 对象的字段保持插入顺序，以保证输出是确定的
+pub enum Json {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+impl Json {
+    // 格式化输出，每一级缩进两个空格
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, "");
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, prefix: &str) {
+        let inner = prefix.to_string() + "  ";
+        match self {
+            Json::Bool(x) => out.push_str(&x.to_string()),
+            Json::Int(x) => out.push_str(&x.to_string()),
+            Json::Str(x) => out.push_str(&json_string(x)),
+            Json::Array(items) if items.is_empty() => out.push_str("[]"),
+            Json::Array(items) => {
+                out.push_str("[\n");
+                for (i, x) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(",\n");
+                    }
+                    out.push_str(&inner);
+                    x.write_pretty(out, &inner);
+                }
+                out.push('\n');
+                out.push_str(prefix);
+                out.push(']');
+            }
+            Json::Object(fields) if fields.is_empty() => out.push_str("{}"),
+            Json::Object(fields) => {
+                out.push_str("{\n");
+                for (i, (key, x)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(",\n");
+                    }
+                    out.push_str(&inner);
+                    out.push_str(&json_string(key));
+                    out.push_str(": ");
+                    x.write_pretty(out, &inner);
+                }
+                out.push('\n');
+                out.push_str(prefix);
+                out.push('}');
+            }
+        }
+    }
+}
+
+// 可以转换为 JSON 的 AST 节点
+// 每个节点都是一个带有 "type" 字段的对象
+pub trait ToJson {
+    fn to_json(&self) -> Json;
+
+    // 以格式化的 JSON 字符串输出
+    fn dump_json(&self) -> String {
+        self.to_json().pretty()
+    }
+}
@@ -0,0 +1,1515 @@
+use crate::error::{LexError, LexErrorKind};
+use crate::escape::{escape_char, unescape, unescape_lenient};
+use crate::interner::StringInterner;
+use crate::parser::TokenSource;
+use crate::token::{is_keyword, BorrowedToken, Token, TokenKind};
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/*
+ * 词法分析器
+ * 把源代码转换为 Token 串，供语法分析使用。
+ * 标识符支持 Unicode（按照 UAX #31 的 XID_Start / XID_Continue）：
+ * Identifier: (XID_Start | '_') XID_Continue* ;
+ */
+
+/////////////////////////////////////////////////////////////////////////
+// 数据流定义
+
+struct CharStream<'a> {
+    source: &'a str,
+    data: Peekable<Chars<'a>>,
+    offset: usize, // 已读取的字节数，用于从源代码中截取 Token 的原文
+    line: u64,
+    col: u64,
+    tab_width: u64, // 制表符占的列数，默认为 1，即与其他字符一样只占一列
+}
+impl<'a> CharStream<'a> {
+    fn new(data: &'a str) -> CharStream<'a> {
+        CharStream {
+            source: data,
+            data: data.chars().peekable(),
+            offset: 0,
+            line: 1,
+            col: 0,
+            tab_width: 1,
+        }
+    }
+
+    fn line(&self) -> u64 {
+        self.line
+    }
+
+    fn col(&self) -> u64 {
+        self.col
+    }
+
+    fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // 从 start 到当前位置的源代码
+    fn slice(&self, start: usize) -> &'a str {
+        &self.source[start..self.offset]
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        self.data.peek()
+    }
+}
+impl Iterator for CharStream<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ch = self.data.next();
+        if let Some(ch) = ch {
+            self.offset += ch.len_utf8();
+            match ch {
+                '\n' => {
+                    self.line += 1;
+                    self.col = 0;
+                }
+                // \r\n 中的 \r 不占列，整个 \r\n 只在读到 \n 时换一次行
+                '\r' if self.data.peek() == Some(&'\n') => {}
+                '\t' => self.col += self.tab_width,
+                _ => self.col += 1,
+            }
+        }
+        ch
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+// 词法分析
+// 当前支持
+// - Identifier, keyword (function, true, false)
+// - Seperator '(' | ')' | '{' | '}' | ';' | ',' | '.' | '[' | ']'
+// - StringLiteral
+// - CharLiteral
+// - IntegerLiteral
+// - Comment (single and block)
+// - Whitespace (only when keep_whitespace is on)
+// - Operator '/' | '/=' | '+' | '++' | '+=' | '-' | '--' | '-=' | '*' | '*=' | '%' | '%='
+//            | '&&' | '||' | '!' | '!=' | '=' | '==' | '<' | '<=' | '>' | '>='
+// - 换行可以是 \n 或 \r\n
+// 尚未支持
+// - 浮点数字面量
+
+pub struct Tokenizer<'a> {
+    stream: CharStream<'a>,
+    eof: bool,
+    keep_comments: bool,   // 为 true 时注释作为 Comment Token 输出，否则直接跳过
+    keep_whitespace: bool, // 为 true 时空白符作为 Whitespace Token 输出，否则直接跳过
+    interner: StringInterner, // Identifier、Keyword、Seperator 与 Operator 的驻留池，相同的 text 共享同一份内存
+    max_tokens: Option<usize>, // 最多输出的 Token 数量（不含 EOF），None 表示不限制
+    count: usize,             // 已经输出的 Token 数量（不含 EOF）
+    token_start: usize,       // 最近读到的 Token 在源代码中的起始偏移
+    lenient_escapes: bool,    // 为 true 时字符串中未知的转义原样保留，否则报错
+}
+impl<'a> Tokenizer<'a> {
+    pub fn new(code: &'a str) -> Tokenizer<'a> {
+        Tokenizer::from_stream(CharStream::new(code))
+    }
+
+    // 保留注释的词法分析器，注释会以 Comment Token 的形式输出
+    pub fn with_comments(code: &'a str) -> Tokenizer<'a> {
+        let mut tokenizer = Tokenizer::new(code);
+        tokenizer.keep_comments = true;
+        tokenizer
+    }
+
+    // 保留空白符的词法分析器，连续的空白符会以 Whitespace Token 的形式输出
+    // 与 with_comments 一起使用时，可以由 Token 串完整地还原出源代码，供保留格式的格式化工具使用
+    pub fn with_whitespace(code: &'a str) -> Tokenizer<'a> {
+        let mut tokenizer = Tokenizer::new(code);
+        tokenizer.keep_whitespace = true;
+        tokenizer
+    }
+
+    // 制表符占 tab_width 列的词法分析器，使报告的列号与以多列显示制表符的编辑器一致
+    pub fn with_tab_width(code: &'a str, tab_width: u64) -> Tokenizer<'a> {
+        let mut tokenizer = Tokenizer::new(code);
+        tokenizer.stream.tab_width = tab_width;
+        tokenizer
+    }
+
+    // 限制 Token 数量的词法分析器，超过 max_tokens 个 Token 后返回错误
+    // 用于防止过大或恶意的输入占用过多内存
+    pub fn with_max_tokens(code: &'a str, max_tokens: usize) -> Tokenizer<'a> {
+        let mut tokenizer = Tokenizer::new(code);
+        tokenizer.max_tokens = Some(max_tokens);
+        tokenizer
+    }
+
+    fn from_stream(stream: CharStream<'a>) -> Tokenizer<'a> {
+        Tokenizer {
+            stream,
+            eof: false,
+            keep_comments: false,
+            keep_whitespace: false,
+            interner: StringInterner::new(),
+            max_tokens: None,
+            count: 0,
+            token_start: 0,
+            lenient_escapes: false,
+        }
+    }
+
+    // 宽松处理转义的词法分析器，字符串中未知的转义（如 \q）原样保留 \ 与其后的字符，而不是报错
+    // 字符字面量只能包含一个字符，其中未知的转义仍然报错
+    pub fn with_lenient_escapes(code: &'a str) -> Tokenizer<'a> {
+        let mut tokenizer = Tokenizer::new(code);
+        tokenizer.lenient_escapes = true;
+        tokenizer
+    }
+
+    // 改为分析新的源代码，保留驻留池与各项设置，只重置读取状态
+    // 供 REPL 等需要反复分析代码的场景使用，避免每次都重新创建词法分析器
+    pub fn reset<'b>(self, code: &'b str) -> Tokenizer<'b> {
+        let mut stream = CharStream::new(code);
+        stream.tab_width = self.stream.tab_width;
+        Tokenizer {
+            stream,
+            eof: false,
+            keep_comments: self.keep_comments,
+            keep_whitespace: self.keep_whitespace,
+            interner: self.interner,
+            max_tokens: self.max_tokens,
+            count: 0,
+            token_start: 0,
+            lenient_escapes: self.lenient_escapes,
+        }
+    }
+
+    // 读取一个语句的 Token，供只重新分析被修改的语句的编辑器使用
+    // 读到最外层的 ';'（包括在内），或者读到使最外层的 '{' 闭合的 '}' 为止，
+    // 因此括号中的 ';'（如 for 语句的头部）与语句块中的语句不会提前结束当前语句
+    // 读到 EOF 时返回已经读到的 Token（不包括 EOF），没有更多的语句时返回空的 Token 串
+    pub fn tokenize_until_semicolon(&mut self) -> Result<Vec<Token>, LexError> {
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+        while let Some(token) = self.next_token() {
+            let token = token?;
+            if token.kind == TokenKind::EOF {
+                break;
+            }
+            let end = match &*token.text {
+                _ if token.kind != TokenKind::Seperator => false,
+                "(" | "{" | "[" => {
+                    depth += 1;
+                    false
+                }
+                ")" | "]" => {
+                    depth -= 1;
+                    false
+                }
+                "}" => {
+                    depth -= 1;
+                    depth <= 0
+                }
+                ";" => depth <= 0,
+                _ => false,
+            };
+            tokens.push(token);
+            if end {
+                break;
+            }
+        }
+        Ok(tokens)
+    }
+
+    // 已经读到的所有 Identifier、Keyword、Seperator 与 Operator 的 text
+    pub fn interner(&self) -> &StringInterner {
+        &self.interner
+    }
+
+    // 从字符串流中获取一个新Token，读到 EOF 之后返回 None
+    // 名称与符号类的 Token 从驻留池中取得 text，重复出现时不再分配内存
+    fn next_token(&mut self) -> Option<Result<Token, LexError>> {
+        let token = match self.next_borrowed()? {
+            Ok(token) => token,
+            Err(e) => return Some(Err(e)),
+        };
+        let text = match token.kind {
+            TokenKind::Identifier
+            | TokenKind::Keyword
+            | TokenKind::Seperator
+            | TokenKind::Operator => self.interner.symbol(&token.text),
+            _ => token.text.into(),
+        };
+        Some(Ok(Token {
+            kind: token.kind,
+            text,
+            line: token.line,
+            col: token.col,
+            span: Some((self.token_start, self.stream.offset())),
+        }))
+    }
+
+    // 与 next_token 相同，但返回的 Token 直接引用源代码，不需要为其分配内存
+    pub fn next_borrowed(&mut self) -> Option<Result<BorrowedToken<'a>, LexError>> {
+        if self.eof {
+            return None;
+        }
+
+        let token = self.read_token();
+        if let (Ok(token), Some(max)) = (&token, self.max_tokens) {
+            if token.kind != TokenKind::EOF {
+                self.count += 1;
+                if self.count > max {
+                    // 超过上限后不再继续分析
+                    self.eof = true;
+                    return Some(Err(LexError::new(
+                        LexErrorKind::TooManyTokens(max),
+                        token.line,
+                        token.col,
+                    )));
+                }
+            }
+        }
+        Some(token)
+    }
+
+    fn read_token(&mut self) -> Result<BorrowedToken<'a>, LexError> {
+        if self.keep_whitespace && matches!(self.stream.peek(), Some(c) if c.is_whitespace()) {
+            let (line, col) = (self.stream.line(), self.stream.col() + 1);
+            let start = self.stream.offset();
+            self.token_start = start;
+            self.skip_whitespaces();
+            return Ok(BorrowedToken {
+                kind: TokenKind::Whitespace,
+                text: self.stream.slice(start).into(),
+                line,
+                col,
+            });
+        }
+
+        // 忽略所有的空白符
+        self.skip_whitespaces();
+        let (line, col) = (self.stream.line(), self.stream.col() + 1);
+        let start = self.stream.offset();
+        self.token_start = start;
+
+        match self.stream.peek() {
+            None => {
+                self.eof = true;
+                Ok(BorrowedToken {
+                    kind: TokenKind::EOF,
+                    text: "".into(),
+                    line,
+                    col,
+                })
+            }
+            Some(&ch) => {
+                match ch {
+                    '"' => return self.parse_string_literal(),
+                    '\'' => return self.parse_char_literal(),
+                    '(' | ')' | '{' | '}' | ';' | ',' | '.' | '[' | ']' => {
+                        self.stream.next();
+                        return Ok(BorrowedToken {
+                            kind: TokenKind::Seperator,
+                            text: self.stream.slice(start).into(),
+                            line,
+                            col,
+                        });
+                    }
+                    '+' => {
+                        // 可能是 +, ++, +=
+                        self.stream.next();
+
+                        return match self.stream.peek() {
+                            Some('+') => {
+                                self.stream.next();
+                                Ok(BorrowedToken {
+                                    kind: TokenKind::Operator,
+                                    text: "++".into(),
+                                    line,
+                                    col,
+                                })
+                            }
+                            Some('=') => {
+                                self.stream.next();
+                                Ok(BorrowedToken {
+                                    kind: TokenKind::Operator,
+                                    text: "+=".into(),
+                                    line,
+                                    col,
+                                })
+                            }
+                            _ => Ok(BorrowedToken {
+                                kind: TokenKind::Operator,
+                                text: "+".into(),
+                                line,
+                                col,
+                            }),
+                        };
+                    }
+                    '-' => {
+                        // 可能是 -, --, -=
+                        self.stream.next();
+
+                        return match self.stream.peek() {
+                            Some('-') => {
+                                self.stream.next();
+                                Ok(BorrowedToken {
+                                    kind: TokenKind::Operator,
+                                    text: "--".into(),
+                                    line,
+                                    col,
+                                })
+                            }
+                            Some('=') => {
+                                self.stream.next();
+                                Ok(BorrowedToken {
+                                    kind: TokenKind::Operator,
+                                    text: "-=".into(),
+                                    line,
+                                    col,
+                                })
+                            }
+                            _ => Ok(BorrowedToken {
+                                kind: TokenKind::Operator,
+                                text: "-".into(),
+                                line,
+                                col,
+                            }),
+                        };
+                    }
+                    '*' | '%' => {
+                        // 可能是 *, *=, %, %=
+                        self.stream.next();
+                        if self.stream.peek() == Some(&'=') {
+                            self.stream.next();
+                        }
+
+                        return Ok(BorrowedToken {
+                            kind: TokenKind::Operator,
+                            text: self.stream.slice(start).into(),
+                            line,
+                            col,
+                        });
+                    }
+                    '&' | '|' => {
+                        // 只支持 &&, ||
+                        self.stream.next();
+
+                        return if self.stream.peek() == Some(&ch) {
+                            self.stream.next();
+                            Ok(BorrowedToken {
+                                kind: TokenKind::Operator,
+                                text: self.stream.slice(start).into(),
+                                line,
+                                col,
+                            })
+                        } else {
+                            Err(LexError::new(LexErrorKind::UnexpectedChar(ch), line, col))
+                        };
+                    }
+                    '!' | '=' | '<' | '>' => {
+                        // 可能是 !, !=, =, ==, <, <=, >, >=
+                        self.stream.next();
+                        if self.stream.peek() == Some(&'=') {
+                            self.stream.next();
+                        }
+
+                        return Ok(BorrowedToken {
+                            kind: TokenKind::Operator,
+                            text: self.stream.slice(start).into(),
+                            line,
+                            col,
+                        });
+                    }
+                    '/' => {
+                        // 可能是 /*, //, /, /=
+                        self.stream.next();
+
+                        return match self.stream.peek() {
+                            Some('/') => {
+                                let text = self.skip_line(start);
+                                if self.keep_comments {
+                                    Ok(BorrowedToken {
+                                        kind: TokenKind::Comment,
+                                        text: text.into(),
+                                        line,
+                                        col,
+                                    })
+                                } else {
+                                    self.read_token()
+                                }
+                            }
+                            Some('*') => {
+                                let text = self.skip_block_comment(start, line, col)?;
+                                if self.keep_comments {
+                                    Ok(BorrowedToken {
+                                        kind: TokenKind::Comment,
+                                        text: text.into(),
+                                        line,
+                                        col,
+                                    })
+                                } else {
+                                    self.read_token()
+                                }
+                            }
+                            Some('=') => {
+                                self.stream.next();
+                                Ok(BorrowedToken {
+                                    kind: TokenKind::Operator,
+                                    text: "/=".into(),
+                                    line,
+                                    col,
+                                })
+                            }
+                            _ => Ok(BorrowedToken {
+                                kind: TokenKind::Operator,
+                                text: "/".into(),
+                                line,
+                                col,
+                            }),
+                        };
+                    }
+                    _ => {}
+                }
+
+                if Tokenizer::is_identifier_start(&ch) {
+                    return Ok(self.parse_identifier());
+                }
+                if ch.is_ascii_digit() {
+                    return self.parse_integer_literal();
+                }
+
+                // 无法识别
+                Err(LexError::new(LexErrorKind::UnexpectedChar(ch), line, col))
+            }
+        }
+    }
+
+    fn skip_whitespaces(&mut self) {
+        while matches!(self.stream.peek(), Some(c) if c.is_whitespace()) {
+            self.stream.next();
+        }
+    }
+
+    // 跳过整行，在解析到 // 后使用
+    // 返回被跳过的注释原文（包含开头的 //，不包含行尾的 \r），start 为注释开头的位置
+    fn skip_line(&mut self, start: usize) -> &'a str {
+        while matches!(self.stream.peek(), Some(&c) if c != '\n') {
+            self.stream.next();
+        }
+        self.stream.slice(start).trim_end_matches('\r')
+    }
+
+    // 跳过段注释
+    // 返回被跳过的注释原文（包含 /* 与 */），start 为注释开头的位置
+    // 如果一直到 EOF 都没有读到 */ 则返回错误，错误位置为注释的开头 (line, col)
+    fn skip_block_comment(
+        &mut self,
+        start: usize,
+        line: u64,
+        col: u64,
+    ) -> Result<&'a str, LexError> {
+        self.stream.next();
+
+        while let Some(&c) = self.stream.peek() {
+            self.stream.next();
+
+            if c == '*' {
+                if let Some(&c) = self.stream.peek() {
+                    if c == '/' {
+                        self.stream.next();
+                        return Ok(self.stream.slice(start));
+                    }
+                } else {
+                    break; // will return error
+                }
+            }
+        }
+
+        Err(LexError::new(LexErrorKind::UnterminatedComment, line, col))
+    }
+
+    // identifier 以 XID_Start 字符或下划线开头，后接若干 XID_Continue 字符（字母、数字、下划线等）
+    fn parse_identifier(&mut self) -> BorrowedToken<'a> {
+        let (line, col) = (self.stream.line(), self.stream.col() + 1);
+        let start = self.stream.offset();
+        self.stream.next(); // 由上层调用保证当前是一个合法的 identifier 开头
+
+        while matches!(self.stream.peek(), Some(x) if Tokenizer::is_identifier_char(x)) {
+            self.stream.next();
+        }
+        let text = self.stream.slice(start);
+
+        match text {
+            _ if is_keyword(text) => BorrowedToken {
+                kind: TokenKind::Keyword,
+                text: text.into(),
+                line,
+                col,
+            },
+            _ => BorrowedToken {
+                kind: TokenKind::Identifier,
+                text: text.into(),
+                line,
+                col,
+            },
+        }
+    }
+
+    fn is_identifier_start(c: &char) -> bool {
+        *c == '_' || unicode_ident::is_xid_start(*c)
+    }
+
+    fn is_identifier_char(c: &char) -> bool {
+        unicode_ident::is_xid_continue(*c)
+    }
+
+    // 整数字面量，为若干个十进制数字
+    // 数字后面紧跟字母或下划线时（如 123abc）返回错误
+    fn parse_integer_literal(&mut self) -> Result<BorrowedToken<'a>, LexError> {
+        let (line, col) = (self.stream.line(), self.stream.col() + 1);
+        let start = self.stream.offset();
+
+        while matches!(self.stream.peek(), Some(x) if x.is_ascii_digit()) {
+            self.stream.next();
+        }
+
+        if matches!(self.stream.peek(), Some(x) if Tokenizer::is_identifier_char(x)) {
+            // 把紧跟的字母一并读掉，整体作为一个非法的数字
+            while matches!(self.stream.peek(), Some(x) if Tokenizer::is_identifier_char(x)) {
+                self.stream.next();
+            }
+            return Err(LexError::new(LexErrorKind::InvalidNumber, line, col));
+        }
+
+        Ok(BorrowedToken {
+            kind: TokenKind::IntegerLiteral,
+            text: self.stream.slice(start).into(),
+            line,
+            col,
+        })
+    }
+
+    // 字符串字面量，表现为 "xxx"
+    // 没有转义字符时 text 直接引用源代码，否则需要另外保存转义后的内容
+    // 当引号未闭合时返回 error，错误位置为起始引号
+    fn parse_string_literal(&mut self) -> Result<BorrowedToken<'a>, LexError> {
+        let (line, col) = (self.stream.line(), self.stream.col() + 1);
+        self.stream.next(); // 忽略起始引号
+        let start = self.stream.offset();
+        let mut has_escape = false;
+
+        // 先找到闭合的引号，再统一做反转义
+        loop {
+            match self.stream.peek() {
+                None | Some('\n') => {
+                    return Err(LexError::new(LexErrorKind::UnterminatedString, line, col))
+                }
+                Some('"') => break,
+                Some('\\') => {
+                    // 转义字符（包括续行的换行）不会结束字符串
+                    has_escape = true;
+                    self.stream.next();
+                    if self.stream.next() == Some('\r') && self.stream.peek() == Some(&'\n') {
+                        self.stream.next();
+                    }
+                }
+                Some(_) => {
+                    self.stream.next();
+                }
+            }
+        }
+
+        let raw = self.stream.slice(start);
+        self.stream.next(); // 闭合引号
+        let text = if has_escape {
+            let unescaped = if self.lenient_escapes {
+                unescape_lenient(raw)
+            } else {
+                unescape(raw)
+            };
+            unescaped
+                .map_err(|e| {
+                    // 把相对于字符串内容的位置换算为源代码中的位置
+                    let col = if e.line == 1 { e.col + col } else { e.col };
+                    LexError::new(e.kind, e.line + line - 1, col)
+                })?
+                .into()
+        } else {
+            raw.into()
+        };
+
+        Ok(BorrowedToken {
+            kind: TokenKind::StringLiteral,
+            text,
+            line,
+            col,
+        })
+    }
+
+    // 字符字面量，表现为 'x'，其中 x 是单个字符或转义字符
+    // text 为字面量所代表的那个字符
+    fn parse_char_literal(&mut self) -> Result<BorrowedToken<'a>, LexError> {
+        let (line, col) = (self.stream.line(), self.stream.col() + 1);
+        self.stream.next(); // 忽略起始引号
+        let start = self.stream.offset();
+
+        let text = match self.stream.peek().copied() {
+            None | Some('\n') => {
+                return Err(LexError::new(LexErrorKind::UnterminatedChar, line, col))
+            }
+            Some('\'') => {
+                self.stream.next();
+                return Err(LexError::new(LexErrorKind::EmptyChar, line, col));
+            }
+            Some('\\') => {
+                let (escape_line, escape_col) = (self.stream.line(), self.stream.col() + 1);
+                self.stream.next();
+                match self.stream.peek().copied() {
+                    None => return Err(LexError::new(LexErrorKind::UnterminatedChar, line, col)),
+                    Some(c) => match escape_char(c) {
+                        Some(x) => {
+                            self.stream.next();
+                            x.to_string().into()
+                        }
+                        None => {
+                            return Err(LexError::new(
+                                LexErrorKind::InvalidEscape(c),
+                                escape_line,
+                                escape_col,
+                            ))
+                        }
+                    },
+                }
+            }
+            Some(_) => {
+                self.stream.next();
+                self.stream.slice(start).into()
+            }
+        };
+
+        match self.stream.peek() {
+            Some('\'') => {
+                self.stream.next();
+                Ok(BorrowedToken {
+                    kind: TokenKind::CharLiteral,
+                    text,
+                    line,
+                    col,
+                })
+            }
+            _ => {
+                // 多于一个字符：跳到同一行中的闭合引号，整体作为一个错误
+                while matches!(self.stream.peek(), Some(&c) if c != '\'' && c != '\n') {
+                    self.stream.next();
+                }
+                if self.stream.peek() == Some(&'\'') {
+                    self.stream.next();
+                    Err(LexError::new(LexErrorKind::MultiChar, line, col))
+                } else {
+                    Err(LexError::new(LexErrorKind::UnterminatedChar, line, col))
+                }
+            }
+        }
+    }
+}
+impl Iterator for Tokenizer<'_> {
+    type Item = Result<Token, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token()
+    }
+}
+
+// 对整段代码做词法分析，返回包括末尾 EOF 在内的所有 Token
+// 遇到词法错误时返回第一个错误
+pub fn tokenize(code: &str) -> Result<Vec<Token>, LexError> {
+    Tokenizer::new(code).collect()
+}
+
+// 对整段代码做词法分析，遇到词法错误时跳过出错的字符继续分析
+// 返回所有的 Token（包括末尾的 EOF）以及所有的错误，对任意输入都保证会结束且不会 panic
+pub fn tokenize_all(code: &str) -> (Vec<Token>, Vec<LexError>) {
+    let mut tokenizer = Tokenizer::new(code);
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+
+    loop {
+        tokenizer.skip_whitespaces();
+        let start = tokenizer.stream.offset();
+        match tokenizer.next_token() {
+            None => break,
+            Some(Ok(token)) => tokens.push(token),
+            Some(Err(e)) => {
+                errors.push(e);
+                // 出错的字符没有被读取时需要跳过它，否则会一直重复同一个错误
+                if tokenizer.stream.offset() == start {
+                    tokenizer.stream.next();
+                }
+            }
+        }
+    }
+
+    (tokens, errors)
+}
+
+// 与 tokenize 相同，但返回的 Token 直接引用源代码
+pub fn tokenize_borrowed(code: &str) -> Result<Vec<BorrowedToken<'_>>, LexError> {
+    let mut tokenizer = Tokenizer::new(code);
+    std::iter::from_fn(|| tokenizer.next_borrowed()).collect()
+}
+
+// 从 BufRead 中逐行读取源代码并做词法分析，不需要把整个文件读入内存
+// 除段注释与续行的字符串外，Token 不会跨越多行，因此每次只需要分析若干完整的行；
+// Token 被行尾截断（报告未结束的注释或字符串）时，继续读入下一行后再分析。
+// 得到的 Token（包括位置与源代码范围）与对整段代码调用 tokenize 相同，遇到错误后结束
+pub struct ReaderTokenizer<R: BufRead> {
+    reader: R,
+    pending: VecDeque<Token>, // 已经分析出但尚未输出的 Token
+    lines: u64,               // 已经分析完的行数
+    offset: usize,            // 已经分析完的字节数
+    done: bool,
+}
+impl<R: BufRead> ReaderTokenizer<R> {
+    pub fn new(reader: R) -> ReaderTokenizer<R> {
+        ReaderTokenizer {
+            reader,
+            pending: VecDeque::new(),
+            lines: 0,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    // 读入若干完整的行并做词法分析，其中的 Token 放入 pending
+    fn read_chunk(&mut self) -> Result<(), LexError> {
+        let mut chunk = String::new();
+        loop {
+            let n = self
+                .reader
+                .read_line(&mut chunk)
+                .map_err(|e| LexError::new(LexErrorKind::Io(e.to_string()), self.lines + 1, 1))?;
+            // 没有以换行结尾说明已经读到了末尾
+            let eof = n == 0 || !chunk.ends_with('\n');
+            match Tokenizer::new(&chunk).collect::<Result<Vec<Token>, LexError>>() {
+                Err(e)
+                    if !eof
+                        && matches!(
+                            e.kind,
+                            LexErrorKind::UnterminatedString | LexErrorKind::UnterminatedComment
+                        ) =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(LexError::new(e.kind, e.line + self.lines, e.col)),
+                Ok(tokens) => {
+                    for mut token in tokens {
+                        // 只有读到末尾时才输出 EOF
+                        if token.kind == TokenKind::EOF && !eof {
+                            continue;
+                        }
+                        token.line += self.lines;
+                        token.span = token.span.map(|(s, e)| (s + self.offset, e + self.offset));
+                        self.pending.push_back(token);
+                    }
+                    self.lines += chunk.matches('\n').count() as u64;
+                    self.offset += chunk.len();
+                    self.done = eof;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+impl<R: BufRead> Iterator for ReaderTokenizer<R> {
+    type Item = Result<Token, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && !self.done {
+            if let Err(e) = self.read_chunk() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+// 供语法分析使用的 Token 流
+
+// 带缓冲的 Token 流
+// 读取过的 Token 会保存在缓冲区中，因此可以回溯到之前的位置重新读取
+pub struct TokenBuffer<I: Iterator<Item = Token>> {
+    tokens: I,
+    buffer: Vec<Token>,
+    pos: usize,
+}
+impl<I: Iterator<Item = Token>> TokenBuffer<I> {
+    pub fn new(tokens: I) -> TokenBuffer<I> {
+        TokenBuffer {
+            tokens,
+            buffer: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+impl<I: Iterator<Item = Token>> TokenSource for TokenBuffer<I> {
+    fn next(&mut self) -> &Token {
+        if self.pos >= self.buffer.len() {
+            match self.tokens.next() {
+                Some(token) => self.buffer.push(token),
+                None => {
+                    // 词法分析器总是以 EOF 结尾，其他来源的 Token 串没有 EOF 时在末尾补上一个
+                    if !matches!(self.buffer.last(), Some(x) if x.kind == TokenKind::EOF) {
+                        let (line, col) = self
+                            .buffer
+                            .last()
+                            .map(|x| (x.line, x.col + x.text.chars().count() as u64))
+                            .unwrap_or((1, 1));
+                        self.buffer.push(Token {
+                            kind: TokenKind::EOF,
+                            text: "".into(),
+                            line,
+                            col,
+                            span: None,
+                        });
+                    }
+                    let last = self.buffer.len() - 1;
+                    return &self.buffer[last];
+                }
+            }
+        }
+
+        let v = &self.buffer[self.pos];
+        self.pos += 1;
+        v
+    }
+
+    fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn trace_back(&mut self, new_pos: usize) -> bool {
+        if new_pos > self.pos {
+            false
+        } else {
+            self.pos = new_pos;
+            true
+        }
+    }
+
+    fn token_at(&self, pos: usize) -> Option<&Token> {
+        self.buffer.get(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_counter::count_allocations;
+    use crate::token::KEYWORDS;
+
+    // 只取 Token 的种类与原文，便于比较
+    fn kinds(code: &str) -> Vec<(TokenKind, String)> {
+        tokenize(code)
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.kind, x.text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        assert_eq!(
+            kinds("café 变量 _x1"),
+            vec![
+                (TokenKind::Identifier, "café".to_string()),
+                (TokenKind::Identifier, "变量".to_string()),
+                (TokenKind::Identifier, "_x1".to_string()),
+                (TokenKind::EOF, "".to_string()),
+            ]
+        );
+        assert_eq!(
+            tokenize("1abc").unwrap_err(),
+            LexError::new(LexErrorKind::InvalidNumber, 1, 1)
+        );
+    }
+
+    #[test]
+    fn tokenize_all_skips_unexpected_char() {
+        let (tokens, errors) = tokenize_all("a @ b");
+        let texts: Vec<&str> = tokens.iter().map(|x| &*x.text).collect();
+        assert_eq!(texts, vec!["a", "b", ""]);
+        assert_eq!(
+            errors,
+            vec![LexError::new(LexErrorKind::UnexpectedChar('@'), 1, 3)]
+        );
+    }
+
+    #[test]
+    fn tokenize_all_returns_on_unterminated_string() {
+        let (tokens, errors) = tokenize_all("f(\"abc");
+        assert_eq!(tokens.last().map(|x| &x.kind), Some(&TokenKind::EOF));
+        assert_eq!(
+            errors,
+            vec![LexError::new(LexErrorKind::UnterminatedString, 1, 3)]
+        );
+    }
+
+    #[test]
+    fn tokenize_all_never_panics() {
+        let inputs = [
+            "", "@", "\"", "'", "''", "'ab'", "/*", "/* *", "&", "|", "\\", "1a", "\"\\",
+            "\"\\q\"", "'\\", "'\\q'", "f(\"a\n", "\r", "\r\n\"", "é@变",
+        ];
+        for x in inputs.iter() {
+            let (tokens, _) = tokenize_all(x);
+            assert_eq!(
+                tokens.last().map(|x| &x.kind),
+                Some(&TokenKind::EOF),
+                "{:?}",
+                x
+            );
+        }
+
+        // 由常见的特殊字符组成的所有长度为 3 的输入
+        let chars = [
+            '"', '\'', '\\', '/', '*', '&', '|', '@', '\n', '\r', ' ', 'a', '1', 'é', '(', ';',
+        ];
+        for a in chars.iter() {
+            for b in chars.iter() {
+                for c in chars.iter() {
+                    let x: String = vec![*a, *b, *c].into_iter().collect();
+                    let (tokens, _) = tokenize_all(&x);
+                    assert_eq!(tokens.last().map(|x| &x.kind), Some(&TokenKind::EOF));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn max_tokens_errors_at_the_limit() {
+        // 恰好 3 个 Token 时不超过上限
+        let tokens: Vec<Token> = Tokenizer::with_max_tokens("a b c", 3)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens.len(), 4);
+
+        let mut tokenizer = Tokenizer::with_max_tokens("a b c d e", 3);
+        for _ in 0..3 {
+            assert!(tokenizer.next().unwrap().is_ok());
+        }
+        assert_eq!(
+            tokenizer.next(),
+            Some(Err(LexError::new(LexErrorKind::TooManyTokens(3), 1, 7)))
+        );
+        assert_eq!(tokenizer.next(), None);
+    }
+
+    #[test]
+    fn reset_lexes_new_source_from_scratch() {
+        let mut tokenizer = Tokenizer::with_max_tokens("let x = \"unterminated", 4);
+        assert!(tokenizer.next().unwrap().is_ok());
+        assert!(tokenizer.next().unwrap().is_ok());
+
+        // 之前的读取位置、行号与计数都不会保留，驻留池与设置则会保留
+        let mut tokenizer = tokenizer.reset("x\n+ y");
+        let tokens: Vec<Token> = tokenizer.by_ref().collect::<Result<_, _>>().unwrap();
+        let tokens: Vec<(&str, u64, u64)> =
+            tokens.iter().map(|x| (&*x.text, x.line, x.col)).collect();
+        assert_eq!(
+            tokens,
+            vec![("x", 1, 1), ("+", 2, 1), ("y", 2, 3), ("", 2, 4)]
+        );
+        assert_eq!(tokenizer.interner().get("let"), Some(0));
+        assert_eq!(tokenizer.interner().get("y"), Some(3));
+
+        let mut tokenizer = tokenizer.reset("a b c d e");
+        let error = tokenizer.find_map(|x| x.err()).unwrap();
+        assert_eq!(error.kind, LexErrorKind::TooManyTokens(4));
+    }
+
+    #[test]
+    fn tokenize_until_semicolon_splits_statements() {
+        // 依次读出每个语句的原文
+        fn statements(code: &str) -> Vec<String> {
+            let mut tokenizer = Tokenizer::new(code);
+            let mut result = Vec::new();
+            loop {
+                let tokens = tokenizer.tokenize_until_semicolon().unwrap();
+                if tokens.is_empty() {
+                    return result;
+                }
+                let texts: Vec<&str> = tokens.iter().map(|x| &*x.text).collect();
+                result.push(texts.join(" "));
+            }
+        }
+
+        assert_eq!(
+            statements("let x = 1; f(x);"),
+            vec!["let x = 1 ;", "f ( x ) ;"]
+        );
+        assert_eq!(
+            statements("function f() { let y = 2; return y; } for (;;) { g(); } h()"),
+            vec![
+                "function f ( ) { let y = 2 ; return y ; }",
+                "for ( ; ; ) { g ( ) ; }",
+                "h ( )",
+            ]
+        );
+    }
+
+    #[test]
+    fn reader_tokenizer_matches_tokenize() {
+        let code = "/* multi\nline */ let s = \"a\\\n b\";\nfunction f() {\n\tprintln(s); // done\n}\nf();";
+        let streamed: Vec<Token> = ReaderTokenizer::new(code.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(streamed, tokenize(code).unwrap());
+
+        // 没有以换行结尾的最后一行，以及出错时的位置
+        let code = "let a = 1;\nlet b = @;";
+        let streamed: Result<Vec<Token>, LexError> =
+            ReaderTokenizer::new(code.as_bytes()).collect();
+        assert_eq!(streamed, tokenize(code));
+    }
+
+    // 每行一个 Token：位置、种类与原文，用于与写在测试中的期望结果比较
+    fn snapshot(code: &str) -> String {
+        tokenize(code)
+            .unwrap()
+            .iter()
+            .map(|x| format!("{}:{} {:?} {:?}\n", x.line, x.col, x.kind, x.text))
+            .collect()
+    }
+
+    #[test]
+    fn golden_hello_world() {
+        let expected = r#"
+1:1 Keyword "function"
+1:10 Identifier "sayHello"
+1:18 Seperator "("
+1:19 Seperator ")"
+1:20 Seperator "{"
+2:5 Identifier "println"
+2:12 Seperator "("
+2:13 StringLiteral "Hello World!"
+2:27 Seperator ")"
+2:28 Seperator ";"
+3:1 Seperator "}"
+4:1 Identifier "sayHello"
+4:9 Seperator "("
+4:10 Seperator ")"
+4:11 Seperator ";"
+4:12 EOF ""
+"#;
+        assert_eq!(
+            snapshot("function sayHello(){\n    println(\"Hello World!\");\n}\nsayHello();"),
+            expected.trim_start()
+        );
+    }
+
+    #[test]
+    fn golden_comments() {
+        let expected = r#"
+2:1 Keyword "let"
+2:5 Identifier "x"
+2:7 Operator "="
+2:9 IntegerLiteral "1"
+2:10 Seperator ";"
+3:12 Identifier "x"
+3:13 Seperator ";"
+3:14 EOF ""
+"#;
+        assert_eq!(
+            snapshot("// line comment\nlet x = 1; /* block\ncomment */ x;"),
+            expected.trim_start()
+        );
+    }
+
+    #[test]
+    fn golden_operators() {
+        let expected = r#"
+1:1 Identifier "a"
+1:3 Operator "+="
+1:6 Identifier "b"
+1:8 Operator "*"
+1:10 Seperator "("
+1:11 Identifier "c"
+1:13 Operator "-"
+1:15 IntegerLiteral "1"
+1:16 Seperator ")"
+1:18 Operator "/"
+1:20 IntegerLiteral "2"
+1:22 Operator "%"
+1:24 IntegerLiteral "3"
+1:25 Seperator ";"
+1:27 Operator "!"
+1:28 Identifier "a"
+1:30 Operator "&&"
+1:33 Identifier "b"
+1:35 Operator "||"
+1:38 Identifier "c"
+1:40 Operator "!="
+1:43 Identifier "d"
+1:44 Seperator ";"
+1:46 Identifier "e"
+1:48 Operator "<="
+1:51 Identifier "f"
+1:53 Operator ">="
+1:56 Identifier "g"
+1:58 Operator "=="
+1:61 Identifier "h"
+1:62 Seperator ";"
+1:63 EOF ""
+"#;
+        assert_eq!(
+            snapshot("a += b * (c - 1) / 2 % 3; !a && b || c != d; e <= f >= g == h;"),
+            expected.trim_start()
+        );
+    }
+
+    #[test]
+    fn golden_string_escapes() {
+        let expected = r#"
+1:1 Identifier "println"
+1:8 Seperator "("
+1:9 StringLiteral "tab\there\n\"quoted\"\\"
+1:34 Seperator ")"
+1:35 Seperator ";"
+1:36 EOF ""
+"#;
+        assert_eq!(
+            snapshot(r#"println("tab\there\n\"quoted\"\\");"#),
+            expected.trim_start()
+        );
+    }
+
+    #[test]
+    fn golden_literals() {
+        let expected = r#"
+1:1 Keyword "let"
+1:5 Identifier "c"
+1:7 Operator "="
+1:9 CharLiteral "x"
+1:12 Seperator ";"
+1:14 Keyword "let"
+1:18 Identifier "n"
+1:20 Operator "="
+1:22 CharLiteral "\n"
+1:26 Seperator ";"
+1:28 Keyword "let"
+1:32 Identifier "i"
+1:34 Operator "="
+1:36 IntegerLiteral "0"
+1:37 Seperator ";"
+1:39 Keyword "return"
+1:46 Keyword "true"
+1:50 Seperator ";"
+1:51 EOF ""
+"#;
+        assert_eq!(
+            snapshot("let c = 'x'; let n = '\\n'; let i = 0; return true;"),
+            expected.trim_start()
+        );
+    }
+
+    #[test]
+    fn golden_loops() {
+        let expected = r#"
+1:1 Keyword "for"
+1:5 Seperator "("
+1:6 Keyword "let"
+1:10 Identifier "i"
+1:12 Operator "="
+1:14 IntegerLiteral "0"
+1:15 Seperator ";"
+1:17 Identifier "i"
+1:19 Operator "<"
+1:21 IntegerLiteral "10"
+1:23 Seperator ";"
+1:25 Identifier "i"
+1:27 Operator "="
+1:29 Identifier "i"
+1:31 Operator "+"
+1:33 IntegerLiteral "1"
+1:34 Seperator ")"
+1:36 Seperator "{"
+1:38 Keyword "while"
+1:44 Seperator "("
+1:45 Keyword "false"
+1:50 Seperator ")"
+1:52 Seperator "{"
+1:53 Seperator "}"
+1:55 Seperator "}"
+1:56 EOF ""
+"#;
+        assert_eq!(
+            snapshot("for (let i = 0; i < 10; i = i + 1) { while (false) {} }"),
+            expected.trim_start()
+        );
+    }
+
+    #[test]
+    fn comments_are_kept_only_on_request() {
+        assert_eq!(
+            kinds("// hi\nfoo"),
+            vec![
+                (TokenKind::Identifier, "foo".to_string()),
+                (TokenKind::EOF, "".to_string()),
+            ]
+        );
+        let tokens: Vec<(TokenKind, String)> = Tokenizer::with_comments("// hi\nfoo /* a\nb */")
+            .map(|x| x.map(|x| (x.kind, x.text.to_string())))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenKind::Comment, "// hi".to_string()),
+                (TokenKind::Identifier, "foo".to_string()),
+                (TokenKind::Comment, "/* a\nb */".to_string()),
+                (TokenKind::EOF, "".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_quotes_in_strings() {
+        assert_eq!(
+            kinds(r#""say \"hi\"""#)[0],
+            (TokenKind::StringLiteral, "say \"hi\"".to_string())
+        );
+        assert_eq!(
+            tokenize(r#""say \"hi\""#).unwrap_err(),
+            LexError::new(LexErrorKind::UnterminatedString, 1, 1)
+        );
+    }
+
+    #[test]
+    fn strings_continue_after_backslash_newline() {
+        assert_eq!(
+            kinds("\"long \\\n     text\" x")[..2],
+            [
+                (TokenKind::StringLiteral, "long text".to_string()),
+                (TokenKind::Identifier, "x".to_string()),
+            ]
+        );
+        assert_eq!(
+            tokenize("\"a\nb\"").unwrap_err(),
+            LexError::new(LexErrorKind::UnterminatedString, 1, 1)
+        );
+    }
+
+    #[test]
+    fn boolean_keywords_are_whole_words() {
+        assert_eq!(
+            kinds("true false truely")[..3],
+            [
+                (TokenKind::Keyword, "true".to_string()),
+                (TokenKind::Keyword, "false".to_string()),
+                (TokenKind::Identifier, "truely".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn borrowed_tokens_match_owned() {
+        let code = "function f(x){ println(\"a\\tb\", x, 'c'); } /* c */ f(-12);";
+        let borrowed: Vec<Token> = tokenize_borrowed(code)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.into_owned())
+            .collect();
+        let owned: Vec<Token> = tokenize(code)
+            .unwrap()
+            .into_iter()
+            .map(|x| Token { span: None, ..x })
+            .collect();
+        assert_eq!(borrowed, owned);
+    }
+
+    #[test]
+    fn borrowed_tokens_do_not_allocate_text() {
+        // 互不相同的标识符无法通过驻留共享内存
+        let code: String = (0..10000).map(|i| format!("f{}();\n", i)).collect();
+        let (tokens, owned) = count_allocations(|| tokenize(&code).unwrap());
+        assert_eq!(tokens.len(), 40001);
+        let (tokens, borrowed) = count_allocations(|| tokenize_borrowed(&code).unwrap());
+        assert_eq!(tokens.len(), 40001);
+        assert!(owned > 10000, "tokenize: {} allocations", owned);
+        assert!(
+            borrowed < 100,
+            "tokenize_borrowed: {} allocations",
+            borrowed
+        );
+    }
+
+    fn lex_error(code: &str) -> LexError {
+        tokenize(code).unwrap_err()
+    }
+
+    #[test]
+    fn unterminated_string() {
+        assert_eq!(
+            lex_error("x \"abc"),
+            LexError::new(LexErrorKind::UnterminatedString, 1, 3)
+        );
+    }
+
+    #[test]
+    fn unterminated_char() {
+        assert_eq!(
+            lex_error("'a"),
+            LexError::new(LexErrorKind::UnterminatedChar, 1, 1)
+        );
+    }
+
+    #[test]
+    fn empty_char() {
+        assert_eq!(
+            lex_error("''"),
+            LexError::new(LexErrorKind::EmptyChar, 1, 1)
+        );
+    }
+
+    #[test]
+    fn multi_char() {
+        assert_eq!(
+            lex_error("'ab'"),
+            LexError::new(LexErrorKind::MultiChar, 1, 1)
+        );
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        assert_eq!(
+            lex_error("x /* never\nclosed"),
+            LexError::new(LexErrorKind::UnterminatedComment, 1, 3)
+        );
+    }
+
+    #[test]
+    fn invalid_escape() {
+        assert_eq!(
+            lex_error("\"a\\qb\""),
+            LexError::new(LexErrorKind::InvalidEscape('q'), 1, 3)
+        );
+    }
+
+    #[test]
+    fn unexpected_char() {
+        assert_eq!(
+            lex_error("f(#)"),
+            LexError::new(LexErrorKind::UnexpectedChar('#'), 1, 3)
+        );
+    }
+
+    #[test]
+    fn invalid_number() {
+        assert_eq!(
+            lex_error("x = 12ab;"),
+            LexError::new(LexErrorKind::InvalidNumber, 1, 5)
+        );
+    }
+
+    #[test]
+    fn too_many_tokens() {
+        let e = Tokenizer::with_max_tokens("a b", 1)
+            .find_map(|x| x.err())
+            .unwrap();
+        assert_eq!(e, LexError::new(LexErrorKind::TooManyTokens(1), 1, 3));
+    }
+
+    #[test]
+    fn io_error() {
+        let e = ReaderTokenizer::new(&b"a\n\xff"[..])
+            .find_map(|x| x.err())
+            .unwrap();
+        assert!(matches!(e.kind, LexErrorKind::Io(_)), "{:?}", e);
+        assert_eq!((e.line, e.col), (2, 1));
+    }
+
+    #[test]
+    fn crlf_advances_the_line_once() {
+        let expected = r#"
+1:1 Identifier "a"
+1:2 Seperator "("
+1:3 Seperator ")"
+1:4 Seperator ";"
+2:1 Identifier "b"
+2:2 Seperator "("
+2:3 Seperator ")"
+2:4 Seperator ";"
+3:1 EOF ""
+"#;
+        assert_eq!(snapshot("a();\r\nb();\r\n"), expected.trim_start());
+        assert_eq!(snapshot("a();\r\nb();\r\n"), snapshot("a();\nb();\n"));
+    }
+
+    #[test]
+    fn lenient_escapes_keep_unknown_sequences() {
+        let code = r#"println("a\qb\n");"#;
+        let tokens: Vec<Token> = Tokenizer::with_lenient_escapes(code)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(&*tokens[2].text, "a\\qb\n");
+        // 默认是严格模式
+        assert_eq!(
+            lex_error(code),
+            LexError::new(LexErrorKind::InvalidEscape('q'), 1, 11)
+        );
+    }
+
+    #[test]
+    fn whitespace_is_kept_only_on_request() {
+        let tokens: Vec<(TokenKind, String)> = Tokenizer::with_whitespace("a  b")
+            .map(|x| x.map(|x| (x.kind, x.text.to_string())))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenKind::Identifier, "a".to_string()),
+                (TokenKind::Whitespace, "  ".to_string()),
+                (TokenKind::Identifier, "b".to_string()),
+                (TokenKind::EOF, "".to_string()),
+            ]
+        );
+        assert_eq!(
+            kinds("a  b"),
+            vec![
+                (TokenKind::Identifier, "a".to_string()),
+                (TokenKind::Identifier, "b".to_string()),
+                (TokenKind::EOF, "".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tab_width_advances_columns() {
+        let positions = |tokenizer: Tokenizer| -> Vec<(u64, u64)> {
+            tokenizer
+                .map(|x| x.map(|x| (x.line, x.col)))
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+        let code = "f();\n\t\tg();";
+        assert_eq!(
+            positions(Tokenizer::with_tab_width(code, 4))[4..],
+            [(2, 9), (2, 10), (2, 11), (2, 12), (2, 13)]
+        );
+        // 默认每个制表符占一列
+        assert_eq!(positions(Tokenizer::new(code))[4], (2, 3));
+    }
+
+    #[test]
+    fn tokenizer_agrees_with_keyword_set() {
+        for x in KEYWORDS {
+            assert_eq!(tokenize(x).unwrap()[0].kind, TokenKind::Keyword, "{}", x);
+        }
+        assert!(tokenize("function").unwrap()[0].is_keyword("function"));
+        assert!(!tokenize("functions").unwrap()[0].is_keyword("function"));
+    }
+}
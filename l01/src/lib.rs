@@ -1,13 +1,48 @@
+#[macro_use]
+extern crate derive_new;
+
+#[cfg(test)]
+mod alloc_counter;
+pub mod analysis;
+pub mod builtins;
+pub mod diagnostics;
+pub mod environment;
 pub mod error;
+pub mod escape;
 pub mod grammar;
+pub mod import;
+pub mod interner;
 pub mod interpreter;
+pub mod json;
+pub mod lexer;
+pub mod optimizer;
+pub mod parser;
+pub mod playground;
 pub mod prog;
 pub mod ref_resolver;
 pub mod token;
+pub mod value;
+pub mod visitor;
 
-pub use error::DecodeError;
-pub use grammar::{Dumper, FunctionBody, FunctionCall, FunctionDecl, Statement};
+pub use analysis::unused_functions;
+pub use diagnostics::{Diagnostic, Diagnostics};
+pub use environment::Environment;
+pub use error::{DecodeError, LexError, LexErrorKind, ParseError, RuntimeError};
+pub use escape::{unescape, unescape_lenient};
+pub use grammar::{
+    Assignment, Block, Dumper, Expression, ForStatement, FunctionBody, FunctionCall, FunctionDecl,
+    ImportStatement, NodeId, ReturnStatement, SourceMap, Statement, VariableDecl, WhileStatement,
+};
+pub use import::resolve_imports;
+pub use interner::StringInterner;
 pub use interpreter::Interpreter;
+pub use json::ToJson;
+pub use lexer::{tokenize, tokenize_all, ReaderTokenizer, TokenBuffer, Tokenizer};
+pub use optimizer::{dead_code, fold_constants};
+pub use parser::{parse_str, Parser, TokenSource};
+pub use playground::run_to_string;
 pub use prog::Prog;
 pub use ref_resolver::RefResolver;
-pub use token::{Token, TokenKind};
+pub use token::{is_keyword, tokens_to_json, BorrowedToken, Token, TokenKind, KEYWORDS};
+pub use value::Value;
+pub use visitor::{Visitor, VisitorMut};
@@ -1,6 +1,3 @@
-#[macro_use]
-extern crate derive_new;
-
 /**
  * 第1节
  * 本节的目的是迅速的实现一个最精简的语言的功能，让你了解一门计算机语言的骨架。
@@ -24,7 +21,7 @@ use l01::DecodeError;
 // 词法分析
 // 本节没有提供词法分析器，直接提供了一个Token串。语法分析程序可以从Token串中依次读出
 // 一个个Token，也可以重新定位Token串的当前读取位置。
-use l01::{Token, TokenKind};
+use l01::{Token, TokenKind, TokenSource};
 
 struct Tokenizer {
     tokens: Vec<Token>,
@@ -36,21 +33,39 @@ struct Tokenizer {
  * 语法分析器从这里获取Token。
  */
 impl Tokenizer {
-    fn new(tokens: Vec<Token>) -> Option<Tokenizer> {
-        if tokens.len() < 1 || tokens.last().unwrap().kind != TokenKind::EOF {
-            None
-        } else {
-            Some(Tokenizer { tokens, pos: 0 })
+    // Token 串应当以 EOF 结尾，没有时（包括空的 Token 串，即空程序）在末尾补上一个
+    // EOF 之后不能再有其他 Token，否则返回错误，保证 EOF 总是最后一个 Token
+    fn new(mut tokens: Vec<Token>) -> Result<Tokenizer, DecodeError> {
+        if let Some(i) = tokens.iter().position(|x| x.kind == TokenKind::EOF) {
+            if let Some(t) = tokens.get(i + 1) {
+                return Err(DecodeError::Fatal(format!(
+                    "unexpected token after EOF: {}",
+                    t
+                )));
+            }
+        }
+        if !matches!(tokens.last(), Some(x) if x.kind == TokenKind::EOF) {
+            let (line, col) = tokens
+                .last()
+                .map(|x| (x.line, x.col + x.text.chars().count() as u64))
+                .unwrap_or((1, 1));
+            tokens.push(Token {
+                kind: TokenKind::EOF,
+                text: "".into(),
+                line,
+                col,
+                span: None,
+            });
         }
+        Ok(Tokenizer { tokens, pos: 0 })
     }
-
-    fn eof(&self) -> bool {
+}
+impl TokenSource for Tokenizer {
+    fn eof(&mut self) -> bool {
         if self.pos >= self.tokens.len() {
             true
-        } else if self.tokens.get(self.pos).unwrap().kind == TokenKind::EOF {
-            true
         } else {
-            false
+            self.tokens.get(self.pos).unwrap().kind == TokenKind::EOF
         }
     }
 
@@ -76,170 +91,17 @@ impl Tokenizer {
             true
         }
     }
+
+    fn token_at(&self, pos: usize) -> Option<&Token> {
+        self.tokens.get(pos)
+    }
 }
 
 /////////////////////////////////////////////////////////////////////////
 // 语法分析
-// 包括了AST的数据结构和递归下降的语法解析程序
-
-use l01::{Dumper, FunctionBody, FunctionCall, FunctionDecl, Prog, Statement};
-
-#[derive(new)]
-struct Parser {
-    tokenizer: Tokenizer,
-}
-impl Parser {
-    fn parse_prog(mut self) -> Result<Prog, DecodeError> {
-        let mut stmts: Vec<Statement> = Vec::new();
-        while !self.tokenizer.eof() {
-            // 每次循环解析一个语句
-
-            // 尝试一下函数声明
-            match self.parse_function_decl() {
-                Ok(stmt) => {
-                    stmts.push(Statement::FunctionDecl(stmt));
-                    continue;
-                }
-                Err(DecodeError::TryNext) => {} // continue
-                Err(DecodeError::Fatal(e)) => return Err(e.into()),
-            }
-
-            // 如果前一个尝试不成功，那么再尝试一下函数调用
-            match self.parse_function_call() {
-                Ok(stmt) => {
-                    stmts.push(Statement::FunctionCall(stmt));
-                    continue;
-                }
-                Err(DecodeError::TryNext) => {} // continue
-                Err(DecodeError::Fatal(e)) => return Err(e.into()),
-            }
-
-            //如果都没成功，那就失败结束
-            return Err("unknown statement".into());
-        }
-
-        Ok(Prog::new(stmts))
-    }
-
-    /**
-     * 解析函数声明
-     * 语法规则：
-     * functionDecl: "function" Identifier "(" ")"  functionBody;
-     */
-    fn parse_function_decl(&mut self) -> Result<FunctionDecl, DecodeError> {
-        let old_pos = self.tokenizer.position();
-        let t = self.tokenizer.next();
-
-        if t.kind == TokenKind::Keyword && t.text == "function" {
-            // "function"
-
-            let t = self.tokenizer.next(); // Identifier
-            if t.kind != TokenKind::Identifier {
-                return Err(format!("expect Identifier but got {:?}", t).into());
-            }
-            let function_name = t.text.to_string();
-
-            // "(",
-            let t = self.tokenizer.next();
-            if t.kind != TokenKind::Seperator || t.text != "(" {
-                return Err(format!("expect Seperator '(' but got {:?}", t).into());
-            }
-            // 暂时不支持参数
-            // ")"
-            let t = self.tokenizer.next();
-            if t.kind != TokenKind::Seperator || t.text != ")" {
-                return Err(format!("expect Seperator ')' but got {:?}", t).into());
-            }
-
-            // 解析函数体
-            let function_body;
-            match self.parse_function_body() {
-                Ok(x) => function_body = x,
-                Err(e) => return Err(e.into()),
-            }
+// AST的数据结构和递归下降的语法解析程序都位于 l01 库中，由各节共用
 
-            // 解析成功
-            return Ok(FunctionDecl::new(function_name, function_body));
-        }
-
-        //如果解析不成功，回溯，继续尝试
-        self.tokenizer.trace_back(old_pos);
-        Err(DecodeError::TryNext)
-    }
-
-    /**
-     * 解析函数体
-     * 语法规则：
-     * functionBody : '{' functionCall* '}' ;
-     */
-    fn parse_function_body(&mut self) -> Result<FunctionBody, String> {
-        let t = self.tokenizer.next();
-        if t.kind != TokenKind::Seperator || t.text != "{" {
-            return Err(format!("expect Seperator '{}' but got {:?}", '{', t));
-        }
-
-        let mut stmts = Vec::new();
-        loop {
-            match self.parse_function_call() {
-                Ok(x) => stmts.push(x),
-                Err(DecodeError::TryNext) => break,
-                Err(DecodeError::Fatal(e)) => return Err(format!("{}", e)),
-            }
-        }
-
-        let t = self.tokenizer.next();
-        if t.kind != TokenKind::Seperator || t.text != "}" {
-            return Err(format!("expect Seperator '{}' but got {:?}", '}', t).into());
-        }
-
-        return Ok(FunctionBody::new(stmts));
-    }
-
-    fn parse_function_call(&mut self) -> Result<FunctionCall, DecodeError> {
-        let old_pos = self.tokenizer.position();
-
-        let t = self.tokenizer.next();
-        if t.kind == TokenKind::Identifier {
-            let function_name = t.text.to_string();
-            let t = self.tokenizer.next();
-            if t.kind == TokenKind::Seperator && t.text == "(" {
-                // function call
-                let mut function_parameters = Vec::new();
-                // parameter, parameter, ... )
-                let mut t = self.tokenizer.next();
-                while t.kind != TokenKind::Seperator || t.text != ")" {
-                    // t should be StringLiteral
-                    if t.kind != TokenKind::StringLiteral {
-                        return Err(format!("expect string parameter '(' but got {:?}", t).into());
-                    }
-                    function_parameters.push(t.text.to_string());
-
-                    // next should be Seperator, ',' or ')'
-                    t = self.tokenizer.next();
-                    if t.kind != TokenKind::Seperator || (t.text != "," && t.text != ")") {
-                        return Err(format!("expect Seperator ',' or ')' but got {:?}", t).into());
-                    }
-                    if t.text == "," {
-                        // simple skip
-                        t = self.tokenizer.next();
-                    }
-                }
-                // 末尾分号
-                let t = self.tokenizer.next();
-                if t.kind != TokenKind::Seperator || t.text != ";" {
-                    return Err(format!("expect Seperator ';' but got {:?}", t).into());
-                }
-
-                // 解析成功
-                return Ok(FunctionCall::new(function_name, function_parameters));
-            }
-        }
-
-        // 回溯
-        self.tokenizer.trace_back(old_pos);
-        Err(DecodeError::TryNext)
-    }
-}
+use l01::{Dumper, Parser};
 
 /////////////////////////////////////////////////////////////////////////
 // 语义分析
@@ -249,7 +111,7 @@ use l01::{Interpreter, RefResolver};
 // 主程序
 fn compile_and_run(tokens: Vec<Token>) -> Result<(), DecodeError> {
     // 词法分析（模拟）
-    let tokenizer = Tokenizer::new(dbg!(tokens)).unwrap();
+    let tokenizer = Tokenizer::new(tokens)?;
 
     // 语法分析
     let mut prog = Parser::new(tokenizer).parse_prog()?;
@@ -263,7 +125,7 @@ fn compile_and_run(tokens: Vec<Token>) -> Result<(), DecodeError> {
 
     // 运行程序
     println!("\n运行程序");
-    Interpreter::run(&prog)?;
+    Interpreter::new().run(&prog)?;
 
     Ok(())
 }
@@ -284,67 +146,115 @@ fn read_token() -> Vec<Token> {
     vec![
         Token {
             kind: TokenKind::Keyword,
-            text: "function".to_string(),
+            text: "function".into(),
+            line: 2,
+            col: 1,
+            span: None,
         },
         Token {
             kind: TokenKind::Identifier,
-            text: "sayHello".to_string(),
+            text: "sayHello".into(),
+            line: 2,
+            col: 10,
+            span: None,
         },
         Token {
             kind: TokenKind::Seperator,
-            text: "(".to_string(),
+            text: "(".into(),
+            line: 2,
+            col: 18,
+            span: None,
         },
         Token {
             kind: TokenKind::Seperator,
-            text: ")".to_string(),
+            text: ")".into(),
+            line: 2,
+            col: 19,
+            span: None,
         },
         Token {
             kind: TokenKind::Seperator,
-            text: "{".to_string(),
+            text: "{".into(),
+            line: 2,
+            col: 20,
+            span: None,
         },
         Token {
             kind: TokenKind::Identifier,
-            text: "println".to_string(),
+            text: "println".into(),
+            line: 3,
+            col: 5,
+            span: None,
         },
         Token {
             kind: TokenKind::Seperator,
-            text: "(".to_string(),
+            text: "(".into(),
+            line: 3,
+            col: 12,
+            span: None,
         },
         Token {
             kind: TokenKind::StringLiteral,
-            text: "Hello World!".to_string(),
+            text: "Hello World!".into(),
+            line: 3,
+            col: 13,
+            span: None,
         },
         Token {
             kind: TokenKind::Seperator,
-            text: ')'.to_string(),
+            text: ")".into(),
+            line: 3,
+            col: 27,
+            span: None,
         },
         Token {
             kind: TokenKind::Seperator,
-            text: ';'.to_string(),
+            text: ";".into(),
+            line: 3,
+            col: 28,
+            span: None,
         },
         Token {
             kind: TokenKind::Seperator,
-            text: '}'.to_string(),
+            text: "}".into(),
+            line: 4,
+            col: 1,
+            span: None,
         },
         Token {
             kind: TokenKind::Identifier,
-            text: "sayHello".to_string(),
+            text: "sayHello".into(),
+            line: 7,
+            col: 1,
+            span: None,
         },
         Token {
             kind: TokenKind::Seperator,
-            text: '('.to_string(),
+            text: "(".into(),
+            line: 7,
+            col: 9,
+            span: None,
         },
         Token {
             kind: TokenKind::Seperator,
-            text: ')'.to_string(),
+            text: ")".into(),
+            line: 7,
+            col: 10,
+            span: None,
         },
         Token {
             kind: TokenKind::Seperator,
-            text: ';'.to_string(),
+            text: ";".into(),
+            line: 7,
+            col: 11,
+            span: None,
         },
         Token {
             kind: TokenKind::EOF,
-            text: "".to_string(),
+            text: "".into(),
+            line: 8,
+            col: 1,
+            span: None,
         },
     ]
 }
@@ -352,3 +262,75 @@ fn read_token() -> Vec<Token> {
 fn main() -> Result<(), DecodeError> {
     compile_and_run(read_token())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use l01::Statement;
+    use l01::{tokenize, TokenBuffer};
+
+    // read_token 模拟的就是这段程序
+    const CODE: &str = "//一个函数的声明，这个函数很简单，只打印\"Hello World!\"\nfunction sayHello(){\n    println(\"Hello World!\");\n}\n\n//调用刚才声明的函数\nsayHello();\n";
+
+    #[test]
+    fn hand_written_tokens_match_tokenizer() {
+        let hand_written = Parser::new(Tokenizer::new(read_token()).unwrap())
+            .parse_prog()
+            .unwrap();
+        let tokenized = Parser::new(TokenBuffer::new(tokenize(CODE).unwrap().into_iter()))
+            .parse_prog()
+            .unwrap();
+        assert_eq!(hand_written, tokenized);
+
+        let tokens: Vec<(TokenKind, String, u64, u64)> = tokenize(CODE)
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.kind, x.text.to_string(), x.line, x.col))
+            .collect();
+        let expected: Vec<(TokenKind, String, u64, u64)> = read_token()
+            .into_iter()
+            .map(|x| (x.kind, x.text.to_string(), x.line, x.col))
+            .collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn tokens_after_eof_are_rejected() {
+        let mut tokens = read_token();
+        tokens.push(tokens[0].clone());
+        let e = Tokenizer::new(tokens).err().unwrap();
+        assert!(matches!(e, DecodeError::Fatal(_)));
+        assert!(
+            e.to_string().starts_with("unexpected token after EOF: "),
+            "{}",
+            e
+        );
+
+        // 没有 EOF 时补上一个
+        let mut tokens = read_token();
+        tokens.pop();
+        assert!(Parser::new(Tokenizer::new(tokens).unwrap())
+            .parse_prog()
+            .is_ok());
+    }
+
+    #[test]
+    fn hand_written_tokens_feed_shared_parser() {
+        let prog = Parser::new(Tokenizer::new(read_token()).unwrap())
+            .parse_prog()
+            .unwrap();
+        assert!(matches!(
+            &prog.stmts[..],
+            [Statement::FunctionDecl(decl), Statement::FunctionCall(call)]
+                if &*decl.name == "sayHello" && decl.body.stmts.len() == 1 && &*call.name == "sayHello"
+        ));
+    }
+
+    #[test]
+    fn empty_token_list_is_an_empty_program() {
+        let prog = Parser::new(Tokenizer::new(vec![]).unwrap())
+            .parse_prog()
+            .unwrap();
+        assert!(prog.stmts.is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+use crate::diagnostics::Diagnostics;
+use crate::grammar::{Expression, Statement};
+use crate::interpreter::Interpreter;
+use crate::prog::Prog;
+use crate::value::Value;
+use crate::visitor::{walk_expression_mut, VisitorMut};
+
+/**
+ * 常量折叠
+ * 把运算数都是字面量的一元、二元运算替换为运算的结果，如 2 + 3 替换为 5，!true 替换为 false。
+ * 含有函数调用或变量的表达式不会被折叠；运算出错（如除以 0）时保留原表达式，留到运行时报错。
+ */
+pub fn fold_constants(prog: &mut Prog) {
+    ConstantFolder.visit_prog_mut(prog);
+}
+
+struct ConstantFolder;
+impl VisitorMut for ConstantFolder {
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        // 先折叠子表达式，这样 1 + 2 * 3 这样的嵌套运算也能折叠为一个字面量
+        walk_expression_mut(self, expr);
+        if let Some(x) = fold(expr) {
+            *expr = x;
+        }
+    }
+}
+
+// 计算运算数都是字面量的运算，不能折叠时返回 None
+fn fold(expr: &Expression) -> Option<Expression> {
+    let value = match expr {
+        Expression::Unary { op, operand } if op == "!" => match constant(operand)? {
+            Value::Bool(x) => Value::Bool(!x),
+            _ => return None,
+        },
+        Expression::Unary { op, operand } => Interpreter::unary(op, constant(operand)?).ok()?,
+        Expression::Binary { op, left, right } => {
+            Interpreter::binary(op, constant(left)?, constant(right)?).ok()?
+        }
+        Expression::Logical { op, left, right } => match (constant(left)?, constant(right)?) {
+            (Value::Bool(l), Value::Bool(r)) if op == "&&" => Value::Bool(l && r),
+            (Value::Bool(l), Value::Bool(r)) => Value::Bool(l || r),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    literal(value)
+}
+
+// 字面量的值，其他表达式返回 None
+fn constant(expr: &Expression) -> Option<Value> {
+    match expr {
+        Expression::StringLiteral(x) => Some(Value::Str(x.to_string())),
+        Expression::IntegerLiteral(x) => Some(Value::Int(*x)),
+        Expression::Char(x) => Some(Value::Char(*x)),
+        Expression::Bool(x) => Some(Value::Bool(*x)),
+        _ => None,
+    }
+}
+
+// 把值转换回字面量，null 没有对应的字面量
+fn literal(value: Value) -> Option<Expression> {
+    match value {
+        Value::Null => None,
+        Value::Bool(x) => Some(Expression::Bool(x)),
+        Value::Int(x) => Some(Expression::IntegerLiteral(x)),
+        Value::Char(x) => Some(Expression::Char(x)),
+        Value::Str(x) => Some(Expression::StringLiteral(x)),
+    }
+}
+
+/**
+ * 检查函数体中 return 之后不可达的语句，对每个不可达的语句给出一条警告
+ * remove 为 true 时同时删除这些语句。函数声明不是可执行的语句，即使位于 return 之后也会保留。
+ * 删除语句会改变函数声明在函数体中的下标，因此需要在引用消解之前执行。
+ */
+pub fn dead_code(prog: &mut Prog, diagnostics: &mut Diagnostics, remove: bool) {
+    // 顶层不允许 return（由引用消解报错），只需要检查各个函数体
+    for x in &mut prog.stmts {
+        if let Statement::FunctionDecl(decl) = x {
+            dead_code_in_body(&mut decl.body.stmts, diagnostics, remove);
+        }
+    }
+}
+
+fn dead_code_in_body(stmts: &mut Vec<Statement>, diagnostics: &mut Diagnostics, remove: bool) {
+    let mut returned_at = None; // 第一个 return 语句的位置
+    let mut unreachable = Vec::new();
+    for (i, x) in stmts.iter_mut().enumerate() {
+        match x {
+            Statement::FunctionDecl(decl) => {
+                dead_code_in_body(&mut decl.body.stmts, diagnostics, remove)
+            }
+            Statement::While(stmt) if returned_at.is_none() => {
+                dead_code_in_body(&mut stmt.body.stmts, diagnostics, remove)
+            }
+            Statement::For(stmt) if returned_at.is_none() => {
+                dead_code_in_body(&mut stmt.body.stmts, diagnostics, remove)
+            }
+            // 空语句什么也不做，不算不可达的代码
+            Statement::Empty(_) => {}
+            x => {
+                if let Some((line, col)) = returned_at {
+                    // 表达式语句没有记录位置，使用 return 的位置
+                    let (line, col) = statement_position(x).unwrap_or((line, col));
+                    diagnostics.warning("unreachable statement".to_string(), line, col);
+                    unreachable.push(i);
+                } else if let Statement::Return(stmt) = x {
+                    returned_at = Some((stmt.line, stmt.col));
+                }
+            }
+        }
+    }
+
+    if remove {
+        let mut i = 0;
+        stmts.retain(|_| {
+            i += 1;
+            !unreachable.contains(&(i - 1))
+        });
+    }
+}
+
+// 语句起始的位置，表达式语句没有记录位置
+fn statement_position(stmt: &Statement) -> Option<(u64, u64)> {
+    match stmt {
+        Statement::FunctionDecl(x) => Some((x.line, x.col)),
+        Statement::FunctionCall(x) => Some((x.line, x.col)),
+        Statement::VariableDecl(x) => Some((x.line, x.col)),
+        Statement::Return(x) => Some((x.line, x.col)),
+        Statement::While(x) => Some((x.line, x.col)),
+        Statement::For(x) => Some((x.line, x.col)),
+        Statement::Assignment(x) => Some((x.line, x.col)),
+        Statement::Import(x) => Some((x.line, x.col)),
+        Statement::ExpressionStatement(_, _) | Statement::Empty(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_str;
+
+    fn folded(code: &str) -> Prog {
+        let mut prog = parse_str(code).unwrap();
+        fold_constants(&mut prog);
+        prog
+    }
+
+    #[test]
+    fn folds_literal_operations() {
+        assert_eq!(
+            folded("let x = 2 + 3 * 4; let y = !true; let z = \"a\" + \"b\";"),
+            parse_str("let x = 14; let y = false; let z = \"ab\";").unwrap()
+        );
+    }
+
+    #[test]
+    fn keeps_calls_variables_and_errors() {
+        let code = "let x = len(\"a\") + 1; let y = x * 2; let z = 1 / 0;";
+        assert_eq!(folded(code), parse_str(code).unwrap());
+        // 调用的参数仍然会被折叠
+        assert_eq!(folded("println(1 + 2);"), parse_str("println(3);").unwrap());
+    }
+
+    #[test]
+    fn unreachable_statements_after_return() {
+        let code = "function f(){\n  return 1;\n  println(2);\n}";
+        let mut prog = parse_str(code).unwrap();
+        let mut diagnostics = Diagnostics::new();
+        dead_code(&mut prog, &mut diagnostics, false);
+        let warnings: Vec<String> = diagnostics.warnings.iter().map(|x| x.to_string()).collect();
+        assert_eq!(warnings, vec!["unreachable statement at 3:3"]);
+        assert_eq!(prog, parse_str(code).unwrap());
+
+        dead_code(&mut prog, &mut Diagnostics::new(), true);
+        assert_eq!(prog, parse_str("function f(){ return 1; }").unwrap());
+    }
+}
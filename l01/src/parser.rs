@@ -0,0 +1,1349 @@
+use crate::diagnostics::Diagnostic;
+use crate::error::{DecodeError, ParseError};
+use crate::grammar::{
+    binding_power, Assignment, Block, Expression, ForStatement, FunctionBody, FunctionCall,
+    FunctionDecl, ImportStatement, NodeId, ReturnStatement, SourceMap, Statement, VariableDecl,
+    WhileStatement,
+};
+use crate::lexer::{tokenize, TokenBuffer};
+use crate::prog::Prog;
+use crate::token::{Token, TokenKind};
+
+/**
+ * Token 的来源
+ * 语法分析器从这里依次读出一个个 Token，也可以重新定位当前的读取位置以实现回溯。
+ */
+pub trait TokenSource {
+    // 读取下一个 Token，读到末尾后总是返回最后一个 Token（即 EOF）
+    fn next(&mut self) -> &Token;
+
+    // 当前的读取位置
+    fn position(&self) -> usize;
+
+    // 回溯到之前的某个位置，不能前进到尚未读取的位置
+    fn trace_back(&mut self, new_pos: usize) -> bool;
+
+    // 已经读取过的位于 pos 处的 Token
+    fn token_at(&self, pos: usize) -> Option<&Token>;
+
+    // 是否已经读到了 EOF
+    fn eof(&mut self) -> bool {
+        let pos = self.position();
+        let eof = self.next().kind == TokenKind::EOF;
+        self.trace_back(pos);
+        eof
+    }
+}
+
+/**
+ * 递归下降的语法解析程序
+ */
+#[derive(new)]
+pub struct Parser<T: TokenSource> {
+    tokenizer: T,
+    #[new(default)]
+    recover: bool, // 为 true 时遇到错误不会停止，而是跳过出错的语句继续解析
+    #[new(default)]
+    errors: Vec<Diagnostic>, // 恢复模式下收集到的错误
+    #[new(default)]
+    last_id: u32, // 最后分配的节点编号
+    #[new(default)]
+    source_map: SourceMap, // 已分配编号的节点在源代码中的范围
+}
+impl<T: TokenSource> Parser<T> {
+    pub fn parse_prog(mut self) -> Result<Prog, DecodeError> {
+        let stmts = self.parse_top_level_statements()?;
+        Ok(Prog::with_source_map(stmts, self.source_map))
+    }
+
+    // 解析整个程序，遇到错误时跳过出错的语句（到下一个 ';' 或 '}' 为止）后继续解析
+    // 返回由解析成功的语句组成的 Prog，以及所有的错误
+    pub fn parse_prog_recovering(mut self) -> (Prog, Vec<Diagnostic>) {
+        self.recover = true;
+        // 恢复模式下出错的语句都被跳过，不会返回错误
+        let stmts = self.parse_top_level_statements().unwrap_or_default();
+        (Prog::with_source_map(stmts, self.source_map), self.errors)
+    }
+
+    // 依次解析顶层的语句直到 EOF，恢复模式下跳过出错的语句，否则在第一个错误处停止
+    fn parse_top_level_statements(&mut self) -> Result<Vec<Statement>, DecodeError> {
+        let mut stmts: Vec<Statement> = Vec::new();
+        while !self.tokenizer.eof() {
+            // 每次循环解析一个语句
+            let checkpoint = self.checkpoint();
+            match self.parse_top_level_statement() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(e) if self.recover => {
+                    self.report(e, checkpoint.0);
+                    self.skip_statement(checkpoint, false);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(stmts)
+    }
+
+    // 解析一个单独的表达式，表达式之后必须是 EOF
+    pub fn parse_standalone_expression(mut self) -> Result<Expression, DecodeError> {
+        let expr = self.parse_expression(0)?;
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::EOF {
+            return Err(ParseError::new("end of expression", t).into());
+        }
+        Ok(expr)
+    }
+
+    fn parse_top_level_statement(&mut self) -> Result<Statement, DecodeError> {
+        // import 语句只能出现在顶层
+        match self.parse_import_statement() {
+            Ok(stmt) => return Ok(Statement::Import(stmt)),
+            Err(DecodeError::TryNext) => {} // continue
+            Err(e) => return Err(e),
+        }
+
+        // 尝试一下函数声明
+        match self.parse_function_decl() {
+            Ok(stmt) => return Ok(Statement::FunctionDecl(stmt)),
+            Err(DecodeError::TryNext) => {} // continue
+            Err(e) => return Err(e),
+        }
+
+        // 如果前一个尝试不成功，那么再尝试一下函数调用或表达式语句
+        match self.parse_statement() {
+            Ok(stmt) => return Ok(stmt),
+            Err(DecodeError::TryNext) => {} // continue
+            Err(e) => return Err(e),
+        }
+
+        //如果都没成功，那就失败结束
+        let t = self.tokenizer.next();
+        Err(format!("unknown statement starting with {}", t).into())
+    }
+
+    // 记录恢复模式下遇到的错误，start 为出错的语句的开头
+    // 语法错误使用读到的 Token 的位置，其他错误使用信息中的位置，信息中没有位置时使用语句开头的位置
+    fn report(&mut self, e: DecodeError, start: usize) {
+        let diagnostic = match e {
+            DecodeError::Parse(e) => Diagnostic::new(
+                format!(
+                    "expect {} but got {:?} '{}'",
+                    e.expected, e.found.kind, e.found.text
+                ),
+                e.line,
+                e.col,
+            ),
+            e => {
+                let message = e.to_string();
+                Diagnostic::from_message(&message).unwrap_or_else(|| {
+                    let (line, col) = self
+                        .tokenizer
+                        .token_at(start)
+                        .map_or((0, 0), |t| (t.line, t.col));
+                    Diagnostic::new(message, line, col)
+                })
+            }
+        };
+        self.errors.push(diagnostic);
+    }
+
+    // 出错后回到语句的开头 checkpoint，撤销其后分配的节点编号，跳过整个语句
+    // 语句在括号层次之外的 ';' 处结束，或在一对完整的 '{' '}' 之后结束（函数声明）
+    // in_body 为 true 时，遇到所在函数体的 '}' 会停在它之前，留给函数体自己处理
+    fn skip_statement(&mut self, checkpoint: (usize, u32), in_body: bool) {
+        self.rollback(checkpoint);
+        let start = checkpoint.0;
+        let mut depth = 0;
+        loop {
+            let pos = self.tokenizer.position();
+            let t = self.tokenizer.next();
+            match (&t.kind, &*t.text) {
+                (TokenKind::EOF, _) => {
+                    self.tokenizer.trace_back(pos);
+                    return;
+                }
+                (TokenKind::Seperator, ";") if depth == 0 => return,
+                (TokenKind::Seperator, "{") => depth += 1,
+                (TokenKind::Seperator, "}") if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                (TokenKind::Seperator, "}") => {
+                    // 不属于本语句的 '}'
+                    if in_body && pos > start {
+                        self.tokenizer.trace_back(pos);
+                    }
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /**
+     * 解析函数声明
+     * 语法规则：
+     * functionDecl: "function" Identifier "(" parameterList? ")"  functionBody;
+     * parameterList : Identifier (',' Identifier)* ;
+     */
+    fn parse_function_decl(&mut self) -> Result<FunctionDecl, DecodeError> {
+        let old_pos = self.tokenizer.position();
+        let t = self.tokenizer.next();
+
+        if t.is_keyword("function") {
+            // "function"
+            let (line, col) = (t.line, t.col);
+
+            let t = self.tokenizer.next(); // Identifier
+            if t.kind != TokenKind::Identifier {
+                return Err(ParseError::new("Identifier", t).into());
+            }
+            let function_name = t.text.clone();
+
+            // "(",
+            let t = self.tokenizer.next();
+            if t.kind != TokenKind::Seperator || &*t.text != "(" {
+                return Err(ParseError::new("Seperator '('", t).into());
+            }
+            // 参数列表
+            let mut params = Vec::new();
+            if self.check_seperator(")") {
+                self.tokenizer.next();
+            } else {
+                loop {
+                    let t = self.tokenizer.next();
+                    if t.kind != TokenKind::Identifier {
+                        return Err(ParseError::new("Identifier", t).into());
+                    }
+                    params.push(t.text.to_string());
+
+                    let t = self.tokenizer.next();
+                    match (&t.kind, &*t.text) {
+                        (TokenKind::Seperator, ",") => {}
+                        (TokenKind::Seperator, ")") => break,
+                        _ => return Err(ParseError::new("Seperator ',' or ')'", t).into()),
+                    }
+                }
+            }
+
+            // 解析函数体
+
+            let function_body = self.parse_function_body(&function_name)?;
+
+            // 解析成功
+            let mut decl = FunctionDecl::new(function_name, params, function_body, line, col);
+            decl.id = self.node_id(old_pos);
+            return Ok(decl);
+        }
+
+        //如果解析不成功，回溯，继续尝试
+        self.tokenizer.trace_back(old_pos);
+        Err(DecodeError::TryNext)
+    }
+
+    /**
+     * 解析函数体
+     * 语法规则：
+     * functionBody : '{' (functionDecl | statement)* '}' ;
+     */
+    fn parse_function_body(&mut self, function_name: &str) -> Result<FunctionBody, DecodeError> {
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Seperator || &*t.text != "{" {
+            return Err(ParseError::new("Seperator '{'", t).into());
+        }
+        let (open_line, open_col) = (t.line, t.col);
+
+        let mut stmts = Vec::new();
+        loop {
+            // 函数体中也可以声明函数，只在该函数体内可见
+            let checkpoint = self.checkpoint();
+            match self.parse_function_decl() {
+                Ok(x) => {
+                    stmts.push(Statement::FunctionDecl(x));
+                    continue;
+                }
+                Err(DecodeError::TryNext) => {} // continue
+                Err(e) if self.recover => {
+                    self.report(e, checkpoint.0);
+                    self.skip_statement(checkpoint, true);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+
+            match self.parse_statement() {
+                Ok(x) => stmts.push(x),
+                Err(DecodeError::TryNext) => break,
+                Err(e) if self.recover => {
+                    self.report(e, checkpoint.0);
+                    self.skip_statement(checkpoint, true);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let t = self.tokenizer.next();
+        if t.kind == TokenKind::EOF {
+            // 程序在函数体中间结束了
+            return Err(format!(
+                "unexpected end of input at {}:{}: expect Seperator '{}' to close the body of function {} opened at {}:{}",
+                t.line, t.col, '}', function_name, open_line, open_col
+            ).into());
+        }
+        if t.kind != TokenKind::Seperator || &*t.text != "}" {
+            return Err(ParseError::new("Seperator '}'", t).into());
+        }
+
+        Ok(FunctionBody::new(stmts))
+    }
+
+    /**
+     * 解析语句（函数声明以外）
+     * 语法规则：
+     * statement : variableDecl | returnStatement | whileStatement | forStatement
+     *           | assignmentStatement | functionCallStatement | expressionStatement | ';' ;
+     */
+    fn parse_statement(&mut self) -> Result<Statement, DecodeError> {
+        if self.check_keyword("import") {
+            let t = self.tokenizer.next();
+            return Err(format!("import is only allowed at the top level, got {}", t).into());
+        }
+
+        // 单独的分号是空语句
+        if self.check_seperator(";") {
+            let start = self.tokenizer.position();
+            self.tokenizer.next();
+            return Ok(Statement::Empty(self.node_id(start)));
+        }
+
+        match self.parse_variable_decl() {
+            Ok(decl) => return Ok(Statement::VariableDecl(decl)),
+            Err(DecodeError::TryNext) => {} // continue
+            Err(e) => return Err(e),
+        }
+
+        match self.parse_return_statement() {
+            Ok(stmt) => return Ok(Statement::Return(stmt)),
+            Err(DecodeError::TryNext) => {} // continue
+            Err(e) => return Err(e),
+        }
+
+        match self.parse_while_statement() {
+            Ok(stmt) => return Ok(Statement::While(stmt)),
+            Err(DecodeError::TryNext) => {} // continue
+            Err(e) => return Err(e),
+        }
+
+        match self.parse_for_statement() {
+            Ok(stmt) => return Ok(Statement::For(stmt)),
+            Err(DecodeError::TryNext) => {} // continue
+            Err(e) => return Err(e),
+        }
+
+        match self.parse_assignment_statement() {
+            Ok(stmt) => return Ok(Statement::Assignment(stmt)),
+            Err(DecodeError::TryNext) => {} // continue
+            Err(e) => return Err(e),
+        }
+
+        match self.parse_function_call() {
+            Ok(call) => return Ok(Statement::FunctionCall(call)),
+            Err(DecodeError::TryNext) => {} // continue
+            Err(e) => return Err(e),
+        }
+
+        self.parse_expression_statement()
+    }
+
+    /**
+     * 解析 import 语句
+     * 语法规则：
+     * importStatement : "import" StringLiteral ';' ;
+     */
+    fn parse_import_statement(&mut self) -> Result<ImportStatement, DecodeError> {
+        if !self.check_keyword("import") {
+            return Err(DecodeError::TryNext);
+        }
+        let start = self.tokenizer.position();
+        let t = self.tokenizer.next(); // "import"
+        let (line, col) = (t.line, t.col);
+
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::StringLiteral {
+            return Err(ParseError::new("a path string after import", t).into());
+        }
+        let path = t.text.to_string();
+
+        // 末尾分号
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Seperator || &*t.text != ";" {
+            return Err(ParseError::new("Seperator ';'", t).into());
+        }
+
+        let mut stmt = ImportStatement::new(path, line, col);
+        stmt.id = self.node_id(start);
+        Ok(stmt)
+    }
+
+    /**
+     * 解析变量声明
+     * 语法规则：
+     * variableDecl : "let" Identifier ('=' expression)? ';' ;
+     */
+    fn parse_variable_decl(&mut self) -> Result<VariableDecl, DecodeError> {
+        if !self.check_keyword("let") {
+            return Err(DecodeError::TryNext);
+        }
+        let start = self.tokenizer.position();
+        let t = self.tokenizer.next(); // "let"
+        let (line, col) = (t.line, t.col);
+
+        let t = self.tokenizer.next(); // Identifier
+        if t.kind != TokenKind::Identifier {
+            return Err(ParseError::new("Identifier", t).into());
+        }
+        let name = t.text.to_string();
+
+        // 可选的初始值
+        let init = if self.check_operator("=") {
+            self.tokenizer.next();
+            Some(self.parse_expression(0)?)
+        } else {
+            None
+        };
+
+        // 末尾分号
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Seperator || &*t.text != ";" {
+            return Err(ParseError::new("Seperator ';'", t).into());
+        }
+
+        let mut decl = VariableDecl::new(name, init, line, col);
+        decl.id = self.node_id(start);
+        Ok(decl)
+    }
+
+    /**
+     * 解析 return 语句
+     * 语法规则：
+     * returnStatement : "return" expression? ';' ;
+     */
+    fn parse_return_statement(&mut self) -> Result<ReturnStatement, DecodeError> {
+        if !self.check_keyword("return") {
+            return Err(DecodeError::TryNext);
+        }
+        let start = self.tokenizer.position();
+        let t = self.tokenizer.next(); // "return"
+        let (line, col) = (t.line, t.col);
+
+        let value = if self.check_seperator(";") {
+            None
+        } else {
+            Some(self.parse_expression(0)?)
+        };
+
+        // 末尾分号
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Seperator || &*t.text != ";" {
+            return Err(ParseError::new("Seperator ';'", t).into());
+        }
+
+        let mut stmt = ReturnStatement::new(value, line, col);
+        stmt.id = self.node_id(start);
+        Ok(stmt)
+    }
+
+    /**
+     * 解析 while 循环
+     * 语法规则：
+     * whileStatement : "while" '(' expression ')' block ;
+     */
+    fn parse_while_statement(&mut self) -> Result<WhileStatement, DecodeError> {
+        if !self.check_keyword("while") {
+            return Err(DecodeError::TryNext);
+        }
+        let start = self.tokenizer.position();
+        let t = self.tokenizer.next(); // "while"
+        let (line, col) = (t.line, t.col);
+
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Seperator || &*t.text != "(" {
+            return Err(ParseError::new("Seperator '('", t).into());
+        }
+        let condition = self.parse_expression(0)?;
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Seperator || &*t.text != ")" {
+            return Err(ParseError::new("Seperator ')'", t).into());
+        }
+
+        let body = self.parse_block()?;
+        let mut stmt = WhileStatement::new(condition, body, line, col);
+        stmt.id = self.node_id(start);
+        Ok(stmt)
+    }
+
+    /**
+     * 解析 for 循环
+     * 语法规则：
+     * forStatement : "for" '(' (variableDecl | assignmentStatement | expressionStatement | ';')
+     *                expression? ';' (assignment | expression)? ')' block ;
+     */
+    fn parse_for_statement(&mut self) -> Result<ForStatement, DecodeError> {
+        if !self.check_keyword("for") {
+            return Err(DecodeError::TryNext);
+        }
+        let start = self.tokenizer.position();
+        let t = self.tokenizer.next(); // "for"
+        let (line, col) = (t.line, t.col);
+
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Seperator || &*t.text != "(" {
+            return Err(ParseError::new("Seperator '('", t).into());
+        }
+
+        // 初始化语句，自带结尾的分号
+        let init = if self.check_seperator(";") {
+            self.tokenizer.next();
+            None
+        } else {
+            Some(match self.parse_variable_decl() {
+                Ok(x) => Statement::VariableDecl(x),
+                Err(DecodeError::TryNext) => match self.parse_assignment_statement() {
+                    Ok(x) => Statement::Assignment(x),
+                    Err(DecodeError::TryNext) => self.parse_expression_statement()?,
+                    Err(e) => return Err(e),
+                },
+                Err(e) => return Err(e),
+            })
+        };
+
+        // 循环条件
+        let condition = if self.check_seperator(";") {
+            None
+        } else {
+            Some(self.parse_expression(0)?)
+        };
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Seperator || &*t.text != ";" {
+            return Err(ParseError::new("Seperator ';'", t).into());
+        }
+
+        // 每次循环后执行的语句，没有结尾的分号
+        let update = if self.check_seperator(")") {
+            None
+        } else {
+            Some(match self.parse_assignment() {
+                Ok(x) => Statement::Assignment(x),
+                Err(DecodeError::TryNext) => {
+                    let start = self.tokenizer.position();
+                    let expr = self.parse_expression(0)?;
+                    Statement::ExpressionStatement(expr, self.node_id(start))
+                }
+                Err(e) => return Err(e),
+            })
+        };
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Seperator || &*t.text != ")" {
+            return Err(ParseError::new("Seperator ')'", t).into());
+        }
+
+        let body = self.parse_block()?;
+        let mut stmt = ForStatement::new(init, condition, update, body, line, col);
+        stmt.id = self.node_id(start);
+        Ok(stmt)
+    }
+
+    /**
+     * 解析赋值语句
+     * 语法规则：
+     * assignmentStatement : assignment ';' ;
+     */
+    fn parse_assignment_statement(&mut self) -> Result<Assignment, DecodeError> {
+        let assignment = self.parse_assignment()?;
+
+        // 末尾分号
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Seperator || &*t.text != ";" {
+            return Err(ParseError::new("Seperator ';'", t).into());
+        }
+
+        Ok(assignment)
+    }
+
+    /**
+     * 解析赋值
+     * 语法规则：
+     * assignment : Identifier ('=' | '+=' | '-=' | '*=' | '/=' | '%=') expression ;
+     * 复合赋值 x op= e 等价于 x = x op e
+     */
+    fn parse_assignment(&mut self) -> Result<Assignment, DecodeError> {
+        let old_pos = self.tokenizer.position();
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Identifier {
+            self.tokenizer.trace_back(old_pos);
+            return Err(DecodeError::TryNext);
+        }
+        let (name, line, col) = (t.text.to_string(), t.line, t.col);
+
+        // 标识符后面不是赋值运算符，回溯，继续尝试其他语句
+        let t = self.tokenizer.next();
+        let op = t.text.to_string();
+        if t.kind != TokenKind::Operator || (op != "=" && compound_operator(&op).is_none()) {
+            self.tokenizer.trace_back(old_pos);
+            return Err(DecodeError::TryNext);
+        }
+
+        let value = self.parse_expression(0)?;
+        let value = desugar_assignment(&name, &op, value);
+        let mut assignment = Assignment::new(name, value, line, col);
+        assignment.id = self.node_id(old_pos);
+        Ok(assignment)
+    }
+
+    /**
+     * 解析语句块
+     * 语法规则：
+     * block : '{' statement* '}' ;
+     */
+    fn parse_block(&mut self) -> Result<Block, DecodeError> {
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Seperator || &*t.text != "{" {
+            return Err(ParseError::new("Seperator '{'", t).into());
+        }
+        let (open_line, open_col) = (t.line, t.col);
+
+        let mut stmts = Vec::new();
+        loop {
+            let checkpoint = self.checkpoint();
+            match self.parse_statement() {
+                Ok(x) => stmts.push(x),
+                Err(DecodeError::TryNext) => break,
+                Err(e) if self.recover => {
+                    self.report(e, checkpoint.0);
+                    self.skip_statement(checkpoint, true);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let t = self.tokenizer.next();
+        if t.kind == TokenKind::EOF {
+            return Err(format!(
+                "unexpected end of input at {}:{}: expect Seperator '{}' to close the block opened at {}:{}",
+                t.line, t.col, '}', open_line, open_col
+            )
+            .into());
+        }
+        if t.kind != TokenKind::Seperator || &*t.text != "}" {
+            return Err(ParseError::new("Seperator '}'", t).into());
+        }
+
+        Ok(Block::new(stmts))
+    }
+
+    /**
+     * 解析表达式语句
+     * 语法规则：
+     * expressionStatement : expression ';' ;
+     */
+    fn parse_expression_statement(&mut self) -> Result<Statement, DecodeError> {
+        // 遇到块的结尾或 EOF 时，交由调用者处理
+        if self.check_seperator("}") || self.tokenizer.eof() {
+            return Err(DecodeError::TryNext);
+        }
+
+        let start = self.tokenizer.position();
+        let expr = self.parse_expression(0)?;
+
+        // 末尾分号
+        let t = self.tokenizer.next();
+        if t.kind != TokenKind::Seperator || &*t.text != ";" {
+            return Err(ParseError::new("Seperator ';'", t).into());
+        }
+
+        Ok(Statement::ExpressionStatement(expr, self.node_id(start)))
+    }
+
+    /**
+     * 解析函数调用语句
+     * 语法规则：
+     * functionCallStatement : functionCall ';' ;
+     */
+    fn parse_function_call(&mut self) -> Result<FunctionCall, DecodeError> {
+        let checkpoint = self.checkpoint();
+        let call = self.parse_call()?;
+
+        // 末尾分号，如果不是分号，说明这是一个更复杂的表达式（如 f() + 1;），回溯后按表达式语句解析
+        // 已经解析的调用会被丢弃，它的编号一并撤销
+        if !self.check_seperator(";") {
+            self.rollback(checkpoint);
+            return Err(DecodeError::TryNext);
+        }
+        self.tokenizer.next();
+
+        Ok(call)
+    }
+
+    /**
+     * 解析函数调用
+     * 语法规则：
+     * functionCall : Identifier '(' parameterList? ')' ;
+     * parameterList : expression (',' expression)* ;
+     */
+    fn parse_call(&mut self) -> Result<FunctionCall, DecodeError> {
+        let old_pos = self.tokenizer.position();
+
+        let t = self.tokenizer.next();
+        if t.kind == TokenKind::Identifier {
+            let function_name = t.text.clone();
+            let (line, col) = (t.line, t.col);
+            let t = self.tokenizer.next();
+            if t.kind == TokenKind::Seperator && &*t.text == "(" {
+                // function call
+                // 调用可能跨越多行，记下 '(' 的位置，缺少 ')' 时一并报告
+                let (open_line, open_col) = (t.line, t.col);
+                let mut function_parameters = Vec::new();
+                // parameter, parameter, ... )
+                // 不允许末尾多余的逗号，如 f("a",)
+                if !self.check_seperator(")") {
+                    loop {
+                        function_parameters.push(self.parse_expression(0)?);
+                        if !self.check_seperator(",") {
+                            break;
+                        }
+                        self.tokenizer.next(); // ","
+                    }
+                }
+
+                // next should be ')'
+                let t = self.tokenizer.next();
+                if t.kind != TokenKind::Seperator || &*t.text != ")" {
+                    let expected = format!(
+                        "Seperator ',' or ')' to close '(' at {}:{}",
+                        open_line, open_col
+                    );
+                    return Err(ParseError::new(&expected, t).into());
+                }
+
+                // 解析成功
+                let mut call = FunctionCall::new(function_name, function_parameters, line, col);
+                call.id = self.node_id(old_pos);
+                return Ok(call);
+            }
+        }
+
+        // 回溯
+        self.tokenizer.trace_back(old_pos);
+        Err(DecodeError::TryNext)
+    }
+
+    /**
+     * 解析表达式
+     * 采用 Pratt 算法（优先级爬升），min_bp 为当前允许的最小左结合力，从 0 开始
+     * 二元运算符的优先级从低到高依次为：||，&&，== !=，< <= > >=，+ -，* / %
+     * 所有二元运算符都是左结合的
+     * 赋值 = 与复合赋值 += 等的优先级最低，并且是右结合的，左侧必须是变量：
+     * assignmentExpression : Identifier ('=' | '+=' | '-=' | '*=' | '/=' | '%=') expression ;
+     */
+    fn parse_expression(&mut self, min_bp: u8) -> Result<Expression, DecodeError> {
+        let mut left = self.parse_unary()?;
+
+        loop {
+            let pos = self.tokenizer.position();
+            let t = self.tokenizer.next();
+            let op = t.text.to_string();
+            let (line, col) = (t.line, t.col);
+            let bp = match t.kind {
+                TokenKind::Operator
+                    if (op == "=" || compound_operator(&op).is_some()) && min_bp == 0 =>
+                {
+                    let name = match left {
+                        Expression::Variable(name) => name,
+                        _ => {
+                            return Err(
+                                format!("invalid assignment target at {}:{}", line, col).into()
+                            )
+                        }
+                    };
+                    let value = self.parse_expression(0)?;
+                    let value = desugar_assignment(&name, &op, value);
+                    left = Expression::Assign {
+                        name,
+                        value: Box::new(value),
+                        line,
+                        col,
+                    };
+                    continue;
+                }
+                TokenKind::Operator => binding_power(&op),
+                _ => None,
+            };
+
+            // 不是二元运算符，或者结合力不够（应由外层处理），都结束当前表达式
+            let right_bp = match bp {
+                Some((left_bp, right_bp)) if left_bp >= min_bp => right_bp,
+                _ => {
+                    self.tokenizer.trace_back(pos);
+                    break;
+                }
+            };
+            let right = self.parse_expression(right_bp)?;
+
+            left = match op.as_str() {
+                "&&" | "||" => Expression::Logical {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+                _ => Expression::Binary {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+            };
+        }
+
+        Ok(left)
+    }
+
+    /**
+     * 解析一元表达式
+     * 语法规则：
+     * unary : ('!' | '-') unary | postfix ;
+     */
+    fn parse_unary(&mut self) -> Result<Expression, DecodeError> {
+        if self.check_operator("!") || self.check_operator("-") {
+            let op = self.tokenizer.next().text.to_string();
+            let operand = self.parse_unary()?;
+            return Ok(Expression::Unary {
+                op,
+                operand: Box::new(operand),
+            });
+        }
+        if self.check_operator("--") {
+            // -- 是一个单独的运算符，而不是两个负号
+            let t = self.tokenizer.next();
+            return Err(format!("unsupported operator {}, use - (-x) instead", t).into());
+        }
+        self.parse_postfix()
+    }
+
+    /**
+     * 解析后缀表达式，包括成员访问与下标
+     * 语法规则：
+     * postfix : primary ('.' Identifier | '[' expression ']')* ;
+     */
+    fn parse_postfix(&mut self) -> Result<Expression, DecodeError> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            if self.check_seperator(".") {
+                let t = self.tokenizer.next();
+                let (line, col) = (t.line, t.col);
+                let t = self.tokenizer.next();
+                if t.kind != TokenKind::Identifier {
+                    return Err(ParseError::new("member name after '.'", t).into());
+                }
+                expr = Expression::Member {
+                    target: Box::new(expr),
+                    field: t.text.to_string(),
+                    line,
+                    col,
+                };
+            } else if self.check_seperator("[") {
+                let t = self.tokenizer.next();
+                let (line, col) = (t.line, t.col);
+                let index = self.parse_expression(0)?;
+                let t = self.tokenizer.next();
+                if t.kind != TokenKind::Seperator || &*t.text != "]" {
+                    return Err(ParseError::new("Seperator ']'", t).into());
+                }
+                expr = Expression::Index {
+                    target: Box::new(expr),
+                    index: Box::new(index),
+                    line,
+                    col,
+                };
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    /**
+     * 解析基础表达式
+     * 语法规则：
+     * primary : functionCall | Identifier | StringLiteral | CharLiteral | IntegerLiteral | BooleanLiteral
+     *         | '(' expression ')' ;
+     * BooleanLiteral : "true" | "false" ;
+     */
+    fn parse_primary(&mut self) -> Result<Expression, DecodeError> {
+        match self.parse_call() {
+            Ok(call) => return Ok(Expression::Call(call)),
+            Err(DecodeError::TryNext) => {} // continue
+            Err(e) => return Err(e),
+        }
+
+        if self.check_seperator("(") {
+            self.tokenizer.next();
+            if self.check_seperator(")") {
+                let t = self.tokenizer.next();
+                return Err(ParseError::new("expression inside parentheses", t).into());
+            }
+
+            // 括号只用于分组，不需要单独的节点
+            let expr = self.parse_expression(0)?;
+            let t = self.tokenizer.next();
+            if t.kind != TokenKind::Seperator || &*t.text != ")" {
+                return Err(ParseError::new("Seperator ')'", t).into());
+            }
+            return Ok(expr);
+        }
+
+        let t = self.tokenizer.next();
+        match t.kind {
+            TokenKind::Identifier => Ok(Expression::Variable(t.text.to_string())),
+            TokenKind::StringLiteral => Ok(Expression::StringLiteral(t.text.to_string())),
+            TokenKind::CharLiteral => match t.text.chars().next() {
+                Some(x) => Ok(Expression::Char(x)),
+                None => Err(format!("invalid char literal {}", t).into()),
+            },
+            TokenKind::IntegerLiteral => match t.text.parse() {
+                Ok(x) => Ok(Expression::IntegerLiteral(x)),
+                Err(_) => Err(format!("invalid integer literal {}", t).into()),
+            },
+            TokenKind::Keyword if t.is_keyword("true") => Ok(Expression::Bool(true)),
+            TokenKind::Keyword if t.is_keyword("false") => Ok(Expression::Bool(false)),
+            _ => Err(ParseError::new("expression", t).into()),
+        }
+    }
+
+    // 下一个 Token 是否为指定的分隔符，不消耗 Token
+    fn check_seperator(&mut self, text: &str) -> bool {
+        self.check_token(TokenKind::Seperator, text)
+    }
+
+    // 记下当前的读取位置与最后分配的节点编号，以便之后回溯
+    fn checkpoint(&self) -> (usize, u32) {
+        (self.tokenizer.position(), self.last_id)
+    }
+
+    // 回溯到 checkpoint 记下的位置，并撤销之后分配的节点编号，避免被丢弃的节点留下编号与源代码范围
+    fn rollback(&mut self, (pos, last_id): (usize, u32)) {
+        self.tokenizer.trace_back(pos);
+        for id in last_id + 1..=self.last_id {
+            self.source_map.remove(&NodeId(id));
+        }
+        self.last_id = last_id;
+    }
+
+    // 为刚刚解析完的节点分配一个新的编号，start 为节点第一个 Token 的位置
+    // Token 带有源代码范围时，同时记下节点在源代码中的范围
+    fn node_id(&mut self, start: usize) -> NodeId {
+        self.last_id += 1;
+        let id = NodeId(self.last_id);
+        let end = self.tokenizer.position();
+        let first = self.tokenizer.token_at(start).and_then(|x| x.span);
+        let last = end
+            .checked_sub(1)
+            .and_then(|x| self.tokenizer.token_at(x))
+            .and_then(|x| x.span);
+        if let (Some((start, _)), Some((_, end))) = (first, last) {
+            self.source_map.insert(id, (start, end));
+        }
+        id
+    }
+
+    // 下一个 Token 是否为指定的关键字，不消耗 Token
+    fn check_keyword(&mut self, keyword: &str) -> bool {
+        self.check_token(TokenKind::Keyword, keyword)
+    }
+
+    // 下一个 Token 是否为指定的运算符，不消耗 Token
+    fn check_operator(&mut self, text: &str) -> bool {
+        self.check_token(TokenKind::Operator, text)
+    }
+
+    fn check_token(&mut self, kind: TokenKind, text: &str) -> bool {
+        let pos = self.tokenizer.position();
+        let t = self.tokenizer.next();
+        let matched = t.kind == kind && &*t.text == text;
+        self.tokenizer.trace_back(pos);
+        matched
+    }
+}
+
+// 复合赋值运算符对应的二元运算符，如 += 对应 +
+fn compound_operator(op: &str) -> Option<&'static str> {
+    match op {
+        "+=" => Some("+"),
+        "-=" => Some("-"),
+        "*=" => Some("*"),
+        "/=" => Some("/"),
+        "%=" => Some("%"),
+        _ => None,
+    }
+}
+
+// 把赋值运算符 op 右侧的 value 转换为赋给变量 name 的值：x op= e 转换为 x op e，= 保持不变
+fn desugar_assignment(name: &str, op: &str, value: Expression) -> Expression {
+    match compound_operator(op) {
+        Some(op) => Expression::Binary {
+            op: op.to_string(),
+            left: Box::new(Expression::Variable(name.to_string())),
+            right: Box::new(value),
+        },
+        None => value,
+    }
+}
+
+// 对源代码做词法分析和语法分析，词法错误也转换为 DecodeError
+pub fn parse_str(code: &str) -> Result<Prog, DecodeError> {
+    let tokens = tokenize(code)?;
+    Parser::new(TokenBuffer::new(tokens.into_iter())).parse_prog()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 源代码映射中的节点编号及其对应的源代码，按编号排序
+    fn spans<'a>(code: &'a str, prog: &Prog) -> Vec<(u32, &'a str)> {
+        let mut spans: Vec<(u32, &str)> = prog
+            .source_map()
+            .iter()
+            .map(|(id, (start, end))| (id.0, &code[*start..*end]))
+            .collect();
+        spans.sort();
+        spans
+    }
+
+    #[test]
+    fn backtracking_discards_node_ids() {
+        let code = "f() + 1;";
+        let prog = parse_str(code).unwrap();
+        assert_eq!(prog.stmts[0].id(), NodeId(2));
+        assert_eq!(spans(code, &prog), vec![(1, "f()"), (2, "f() + 1;")]);
+    }
+
+    #[test]
+    fn every_statement_has_an_id() {
+        let code = "; 1 + 2; for (;; i + 1) {}";
+        let prog = parse_str(code).unwrap();
+        let ids: Vec<u32> = prog.stmts.iter().map(|x| x.id().0).collect();
+        assert_eq!(ids, vec![1, 2, 4]);
+        assert_eq!(
+            spans(code, &prog),
+            vec![
+                (1, ";"),
+                (2, "1 + 2;"),
+                (3, "i + 1"),
+                (4, "for (;; i + 1) {}")
+            ]
+        );
+    }
+
+    #[test]
+    fn recovery_discards_node_ids() {
+        let code = "f(g(1) h; x = 1;";
+        let (prog, errors) = Parser::new(TokenBuffer::new(tokenize(code).unwrap().into_iter()))
+            .parse_prog_recovering();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(spans(code, &prog), vec![(1, "x = 1")]);
+    }
+
+    #[test]
+    fn parses_to_hand_built_prog() {
+        let prog =
+            parse_str("function greet(name) { println(\"hi \" + name); }\ngreet(\"x\");").unwrap();
+        let println = FunctionCall::new(
+            "println".into(),
+            vec![Expression::Binary {
+                op: "+".to_string(),
+                left: Box::new(Expression::StringLiteral("hi ".to_string())),
+                right: Box::new(Expression::Variable("name".to_string())),
+            }],
+            0,
+            0,
+        );
+        let expected = Prog::new(vec![
+            Statement::FunctionDecl(FunctionDecl::new(
+                "greet".into(),
+                vec!["name".to_string()],
+                FunctionBody::new(vec![Statement::FunctionCall(println)]),
+                0,
+                0,
+            )),
+            Statement::FunctionCall(FunctionCall::new(
+                "greet".into(),
+                vec![Expression::StringLiteral("x".to_string())],
+                0,
+                0,
+            )),
+        ]);
+        assert_eq!(prog, expected);
+    }
+
+    #[test]
+    fn equality_ignores_layout() {
+        let a = parse_str("let x = 1 + 2; x = x * 3;").unwrap();
+        let b = parse_str("\n\n  let x =\n1+2;\nx=x*3;").unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, parse_str("let x = 1 + 2; x = x * 4;").unwrap());
+    }
+
+    #[test]
+    fn function_decl_span() {
+        let code = "let a = 1;\nfunction f(x) { return x; }\nf(a);";
+        let prog = parse_str(code).unwrap();
+        let (start, end) = prog.source_map()[&prog.stmts[1].id()];
+        assert_eq!(&code[start..end], "function f(x) { return x; }");
+    }
+
+    #[test]
+    fn recovery_reports_every_malformed_statement() {
+        let code = "let x = ;\nprintln(\"ok\");\nfunction f( { }\nprintln(x;";
+        let (prog, errors) = Parser::new(TokenBuffer::new(tokenize(code).unwrap().into_iter()))
+            .parse_prog_recovering();
+        let errors: Vec<String> = errors.iter().map(|x| x.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "expect expression but got Seperator ';' at 1:9",
+                "expect Identifier but got Seperator '{' at 3:13",
+                "expect Seperator ',' or ')' to close '(' at 4:8 but got Seperator ';' at 4:10",
+            ]
+        );
+        assert_eq!(prog, parse_str("println(\"ok\");").unwrap());
+
+        // 非恢复模式在第一个错误处停止
+        let e = parse_str(code).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "expect expression but got Seperator ';' at 1:9"
+        );
+    }
+
+    #[test]
+    fn errors_report_position() {
+        let e = parse_str("sayHello(\"a\",\n  \"b\";").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "expect Seperator ',' or ')' to close '(' at 1:9 but got Seperator ';' at 2:6"
+        );
+        match e {
+            DecodeError::Parse(e) => assert_eq!((e.line, e.col), (2, 6)),
+            e => panic!("unexpected error {:?}", e),
+        }
+
+        let e = parse_str("function f(a {}").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "expect Seperator ',' or ')' but got Seperator '{' at 1:14"
+        );
+        let e = parse_str("function f() {\n  g();\n").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "unexpected end of input at 3:1: expect Seperator '}' to close the body of function f opened at 1:14"
+        );
+    }
+
+    #[test]
+    fn assignment_and_call_at_statement_start() {
+        let prog = parse_str("x = 1; x();").unwrap();
+        assert!(matches!(&prog.stmts[0], Statement::Assignment(x) if x.name == "x"));
+        assert!(matches!(&prog.stmts[1], Statement::FunctionCall(x) if &*x.name == "x"));
+    }
+
+    #[test]
+    fn expression_statements() {
+        let prog = parse_str("2 + 3; myVar;").unwrap();
+        assert_eq!(
+            prog.stmts,
+            vec![
+                Statement::ExpressionStatement(
+                    Expression::Binary {
+                        op: "+".to_string(),
+                        left: Box::new(Expression::IntegerLiteral(2)),
+                        right: Box::new(Expression::IntegerLiteral(3)),
+                    },
+                    NodeId::default()
+                ),
+                Statement::ExpressionStatement(
+                    Expression::Variable("myVar".to_string()),
+                    NodeId::default()
+                ),
+            ]
+        );
+        assert!(parse_str("myVar").is_err());
+    }
+
+    #[test]
+    fn trailing_commas_are_rejected() {
+        let arguments = |code: &str| match parse_str(code).unwrap().stmts.remove(0) {
+            Statement::FunctionCall(call) => call.parameters.len(),
+            x => panic!("unexpected statement {:?}", x),
+        };
+        assert_eq!(arguments("f();"), 0);
+        assert_eq!(arguments("f(\"a\");"), 1);
+        assert_eq!(
+            parse_str("f(\"a\",);").unwrap_err().to_string(),
+            "expect expression but got Seperator ')' at 1:7"
+        );
+
+        assert!(parse_str("function f(){} function g(a){} function h(a, b){}").is_ok());
+        assert_eq!(
+            parse_str("function f(a,){}").unwrap_err().to_string(),
+            "expect Identifier but got Seperator ')' at 1:14"
+        );
+    }
+
+    #[test]
+    fn calls_split_across_lines() {
+        assert_eq!(
+            parse_str("println(\n  \"hi\"\n);").unwrap(),
+            parse_str("println(\"hi\");").unwrap()
+        );
+
+        // 错误指向缺少 ) 的那一行，并给出 ( 所在的位置
+        let e = parse_str("println(\n  \"hi\"\n;").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "expect Seperator ',' or ')' to close '(' at 1:8 but got Seperator ';' at 3:1"
+        );
+    }
+
+    #[test]
+    fn unterminated_function_body() {
+        // 报告输入结束的位置，而不是进入函数体之前读到的 token
+        let e = parse_str("function f(){ println(\"x\");").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "unexpected end of input at 1:28: expect Seperator '}' to close the body of function f opened at 1:13"
+        );
+    }
+
+    #[test]
+    fn token_buffer_rewinds() {
+        let tokens = tokenize("f(x);").unwrap();
+        let mut buffer = TokenBuffer::new(tokens.into_iter());
+        let mark = buffer.position();
+        assert_eq!(&*buffer.next().text, "f");
+        assert_eq!(&*buffer.next().text, "(");
+        assert!(buffer.trace_back(mark));
+        assert_eq!(&*buffer.next().text, "f");
+        // 不能前进到还没有读过的位置
+        assert!(!buffer.trace_back(5));
+    }
+
+    #[test]
+    fn identifier_led_statements_backtrack() {
+        // 以标识符开头但不是调用的语句，回溯后依次尝试赋值与表达式语句
+        let prog = parse_str("f = 1;\nf + 1;\nf(1);").unwrap();
+        assert!(matches!(&prog.stmts[0], Statement::Assignment(x) if x.name == "f"));
+        assert!(matches!(
+            &prog.stmts[1],
+            Statement::ExpressionStatement(Expression::Binary { .. }, _)
+        ));
+        assert!(matches!(&prog.stmts[2], Statement::FunctionCall(x) if &*x.name == "f"));
+    }
+
+    #[test]
+    fn member_access() {
+        let tokens = tokenize("a.b").unwrap();
+        let kinds: Vec<(TokenKind, &str)> =
+            tokens.iter().map(|x| (x.kind.clone(), &*x.text)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (TokenKind::Identifier, "a"),
+                (TokenKind::Seperator, "."),
+                (TokenKind::Identifier, "b"),
+                (TokenKind::EOF, ""),
+            ]
+        );
+
+        let prog = parse_str("a.b.c;").unwrap();
+        match &prog.stmts[0] {
+            Statement::ExpressionStatement(Expression::Member { target, field, .. }, _) => {
+                assert_eq!(field, "c");
+                assert!(
+                    matches!(target.as_ref(), Expression::Member { field, .. } if field == "b")
+                );
+            }
+            x => panic!("unexpected statement {:?}", x),
+        }
+    }
+
+    #[test]
+    fn index_expressions() {
+        let tokens = tokenize("a[0]").unwrap();
+        let texts: Vec<&str> = tokens.iter().map(|x| &*x.text).collect();
+        assert_eq!(texts, vec!["a", "[", "0", "]", ""]);
+        assert_eq!(tokens[1].kind, TokenKind::Seperator);
+        assert_eq!(tokens[3].kind, TokenKind::Seperator);
+
+        let prog = parse_str("a[i + 1];").unwrap();
+        match &prog.stmts[0] {
+            Statement::ExpressionStatement(Expression::Index { target, index, .. }, _) => {
+                assert_eq!(target.as_ref(), &Expression::Variable("a".to_string()));
+                assert!(matches!(index.as_ref(), Expression::Binary { op, .. } if op == "+"));
+            }
+            x => panic!("unexpected statement {:?}", x),
+        }
+        assert!(parse_str("a[];").is_err());
+    }
+
+    #[test]
+    fn malformed_input_is_an_error_not_a_panic() {
+        // 没有 EOF 的 Token 串
+        let tokens = tokenize("f(").unwrap();
+        let without_eof = tokens.into_iter().filter(|x| x.kind != TokenKind::EOF);
+        assert!(Parser::new(TokenBuffer::new(without_eof))
+            .parse_prog()
+            .is_err());
+        let empty = Parser::new(TokenBuffer::new(Vec::new().into_iter()))
+            .parse_prog()
+            .unwrap();
+        assert!(empty.stmts.is_empty());
+
+        for code in &[
+            "println(\"abc",
+            "f(",
+            "(",
+            "function",
+            "let",
+            "let x =",
+            "\"",
+            "'",
+        ] {
+            assert!(parse_str(code).is_err(), "{:?}", code);
+        }
+    }
+
+    #[test]
+    fn token_stream_without_eof_still_parses() {
+        let code = "sayHello();";
+        let tokens = tokenize(code).unwrap();
+        let without_eof: Vec<Token> = tokens
+            .into_iter()
+            .filter(|x| x.kind != TokenKind::EOF)
+            .collect();
+        let prog = Parser::new(TokenBuffer::new(without_eof.into_iter()))
+            .parse_prog()
+            .unwrap();
+        assert_eq!(prog, parse_str(code).unwrap());
+    }
+
+    #[test]
+    fn stray_semicolons_are_empty_statements() {
+        let prog = parse_str("sayHello();;").unwrap();
+        assert!(matches!(prog.stmts[0], Statement::FunctionCall(_)));
+        assert!(matches!(prog.stmts[1], Statement::Empty(_)));
+
+        let prog = parse_str(";").unwrap();
+        assert!(matches!(prog.stmts[..], [Statement::Empty(_)]));
+        // 调用之后仍然需要 ;
+        assert!(parse_str("sayHello()").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::interpreter::{Interpreter, STACK_SIZE};
+use crate::parser::parse_str;
+use crate::ref_resolver::RefResolver;
+use std::panic;
+use std::thread;
+
+// 执行代码，以字符串的形式返回程序的输出，出错时返回 "error: " 开头的错误信息
+// 不会向标准输出打印任何内容，便于嵌入到其他环境（如浏览器中的 playground）
+// eprintln 的输出追加在正常输出之后
+// 调用方的线程栈可能很小（如默认 2MB 的子线程），因此在 STACK_SIZE 大小的栈上执行，
+// 使递归过深的程序报告调用深度超限，而不是让宿主栈溢出；不支持线程的环境（如 wasm）中直接执行
+pub fn run_to_string(code: &str) -> String {
+    thread::scope(|scope| {
+        match thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || run_here(code))
+        {
+            Ok(handle) => handle.join().unwrap_or_else(|e| panic::resume_unwind(e)),
+            Err(_) => run_here(code),
+        }
+    })
+}
+
+// 在当前线程上执行
+fn run_here(code: &str) -> String {
+    let mut out = Vec::new();
+    let mut err = Vec::new();
+    let result = parse_str(code)
+        .map_err(|e| e.to_string())
+        .and_then(|mut prog| {
+            RefResolver::resolve(&mut prog)?;
+            Interpreter::new().run_with_writers(&prog, &mut out, &mut err)
+        });
+
+    out.extend(err);
+    match result {
+        Ok(()) => String::from_utf8_lossy(&out).into_owned(),
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+// 供浏览器调用的入口，用 wasm-pack 构建时启用 wasm 特性
+#[cfg(feature = "wasm")]
+mod wasm {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    pub fn run(code: &str) -> String {
+        super::run_to_string(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_output() {
+        assert_eq!(
+            run_to_string("function f(){ println(\"hi\"); } f(); print(1 + 2);"),
+            "hi\n3"
+        );
+    }
+
+    #[test]
+    fn formats_errors() {
+        assert_eq!(run_to_string("f();"), "error: unkown function f at 1:1");
+        assert_eq!(
+            run_to_string("f("),
+            "error: expect expression but got EOF '' at 1:3"
+        );
+    }
+
+    #[test]
+    fn deep_recursion_on_a_small_stack() {
+        // 默认栈大小的子线程上执行，调用过深时报错而不是栈溢出
+        let output = thread::spawn(|| run_to_string("function f(){ f(); } f();"))
+            .join()
+            .unwrap();
+        assert_eq!(
+            output,
+            "error: stack overflow: max call depth exceeded at 1:15"
+        );
+    }
+}
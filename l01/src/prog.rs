@@ -1,21 +1,223 @@
-use crate::grammar::{Dumper, Statement};
+use crate::grammar::{next_level, Dumper, FunctionCall, FunctionDecl, SourceMap, Statement};
+use crate::json::{Json, ToJson};
+use crate::visitor::{walk_function_call, walk_function_decl, Visitor};
+use std::fmt::{self, Write};
+
+// 程序入口函数的名称
+pub const ENTRY_POINT: &str = "main";
 
 /**
  * 程序节点，也是AST的根节点
  */
+#[derive(Debug, Clone)]
 pub struct Prog {
     pub stmts: Vec<Statement>, //程序中可以包含多个语句
+    source_map: SourceMap,     //语法树节点在源代码中的范围，不是解析得到的程序为空
+}
+impl PartialEq for Prog {
+    // 只比较语法结构，忽略源代码映射、位置与节点编号，手工构造的程序可以与解析得到的程序比较
+    fn eq(&self, other: &Self) -> bool {
+        self.stmts == other.stmts
+    }
 }
 impl Prog {
     pub fn new(stmts: Vec<Statement>) -> Prog {
-        Prog { stmts }
+        Prog::with_source_map(stmts, SourceMap::new())
+    }
+
+    pub fn with_source_map(stmts: Vec<Statement>, source_map: SourceMap) -> Prog {
+        Prog { stmts, source_map }
+    }
+
+    // 节点编号到源代码字节范围的映射，用 &code[start..end] 即可取得节点对应的源代码
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    // 获取声明路径为 path 的函数声明，该路径即 FunctionCall::definition
+    pub fn function_decl(&self, path: &[usize]) -> Option<&FunctionDecl> {
+        let (first, rest) = path.split_first()?;
+        let mut decl = match self.stmts.get(*first) {
+            Some(Statement::FunctionDecl(decl)) => decl,
+            _ => return None,
+        };
+        for i in rest {
+            decl = match decl.body.stmts.get(*i) {
+                Some(Statement::FunctionDecl(x)) => x,
+                _ => return None,
+            };
+        }
+        Some(decl)
+    }
+
+    // 程序中所有的函数调用，包括函数体中与参数中的调用
+    // 按源代码中出现的顺序排列，调用本身排在其参数中的调用之前
+    pub fn calls(&self) -> impl Iterator<Item = &FunctionCall> {
+        let mut visitor = Calls { calls: Vec::new() };
+        visitor.visit_prog(self);
+        visitor.calls.into_iter()
+    }
+
+    // 程序中声明的所有函数的名称，包括函数体中声明的函数
+    // 按源代码中出现的顺序排列，外层函数排在其中声明的函数之前
+    pub fn functions(&self) -> Vec<&str> {
+        let mut visitor = Functions { names: Vec::new() };
+        visitor.visit_prog(self);
+        visitor.names
+    }
+
+    // 入口函数 main 在 stmts 中的下标，没有声明 main 时返回 None
+    // 只有顶层声明的 main 才是入口函数
+    pub fn entry_point(&self) -> Option<usize> {
+        self.stmts
+            .iter()
+            .position(|x| matches!(x, Statement::FunctionDecl(decl) if &*decl.name == ENTRY_POINT))
+    }
+}
+// 按遍历的顺序收集所有的函数调用
+struct Calls<'ast> {
+    calls: Vec<&'ast FunctionCall>,
+}
+impl<'ast> Visitor<'ast> for Calls<'ast> {
+    fn visit_function_call(&mut self, call: &'ast FunctionCall) {
+        self.calls.push(call);
+        walk_function_call(self, call);
+    }
+}
+
+// 按遍历的顺序收集所有函数声明的名称
+struct Functions<'ast> {
+    names: Vec<&'ast str>,
+}
+impl<'ast> Visitor<'ast> for Functions<'ast> {
+    fn visit_function_decl(&mut self, decl: &'ast FunctionDecl) {
+        self.names.push(&decl.name);
+        walk_function_decl(self, decl);
     }
 }
+
 impl Dumper for Prog {
-    fn dump(&self, prefix: &str) {
-        println!("{}Prog", prefix);
+    fn dump_to(&self, w: &mut dyn Write, prefix: &str, indent: &str) -> fmt::Result {
+        writeln!(w, "{}Prog", prefix)?;
         for x in &self.stmts {
-            x.dump(&(prefix.to_string() + "\t"))
+            x.dump_to(w, &next_level(prefix, indent), indent)?;
         }
+        Ok(())
+    }
+}
+impl ToJson for Prog {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("type", Json::Str("Prog".to_string())),
+            (
+                "stmts",
+                Json::Array(self.stmts.iter().map(|x| x.to_json()).collect()),
+            ),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Diagnostics;
+    use crate::parser::parse_str;
+    use crate::ref_resolver::RefResolver;
+
+    #[test]
+    fn calls_include_bodies_and_arguments() {
+        let prog = parse_str(
+            "function sayHello(){ println(\"Hello World!\"); }\nsayHello();\nprintln(str(len(\"x\")));",
+        )
+        .unwrap();
+        let names: Vec<&str> = prog.calls().map(|x| &*x.name).collect();
+        assert_eq!(names, vec!["println", "sayHello", "println", "str", "len"]);
+    }
+
+    #[test]
+    fn functions_include_nested_declarations() {
+        let prog = parse_str("function a(){ function b(){} } function c(){}").unwrap();
+        assert_eq!(prog.functions(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn dump_ast_golden() {
+        let prog = parse_str(
+            "function greet(name){ let s = \"hi \" + name; println(s); }\nwhile (false) { greet(\"x\"); }",
+        )
+        .unwrap();
+        let expected = r#"
+Prog
+  FunctionDecl greet, params: name
+    FunctionBody
+      VariableDecl s
+        Binary +
+          StringLiteral "hi "
+          Variable name
+      FunctionCall println, builtin
+        Variable s
+  While
+    Bool false
+    Block
+      FunctionCall greet [UNRESOLVED]
+        StringLiteral "x"
+"#;
+        assert_eq!(prog.dump_string("", "  "), expected.trim_start());
+    }
+
+    #[test]
+    fn json_dump_marks_resolved_calls() {
+        let mut prog = parse_str("function f(){} f();").unwrap();
+        assert!(prog.dump_json().contains("\"resolved\": false"));
+        RefResolver::resolve(&mut prog).unwrap();
+        let json = prog.dump_json();
+        assert!(json.contains("\"name\": \"f\""));
+        assert!(json.contains("\"resolved\": true"));
+        assert!(!json.contains("\"resolved\": false"));
+    }
+
+    #[test]
+    fn dump_indent_is_configurable() {
+        let prog = parse_str("function f(){ println(1); }").unwrap();
+        let tabs = prog.dump_string("", "\t");
+        assert_eq!(
+            tabs,
+            "Prog\n\tFunctionDecl f\n\t\tFunctionBody\n\t\t\tFunctionCall println, builtin\n\t\t\t\tIntegerLiteral 1\n"
+        );
+        assert_eq!(tabs.replace('\t', "  "), prog.dump_string("", "  "));
+    }
+
+    #[test]
+    fn hello_world_functions() {
+        let prog =
+            parse_str("function sayHello(){ println(\"Hello World!\"); }\nsayHello();").unwrap();
+        assert_eq!(prog.functions(), vec!["sayHello"]);
+    }
+
+    #[test]
+    fn dump_marks_unresolved_calls() {
+        let mut prog = parse_str("function f(){}\nf();\nundefined();").unwrap();
+        let mut diagnostics = Diagnostics::new();
+        assert!(RefResolver::resolve_with_diagnostics(&mut prog, &mut diagnostics).is_err());
+        let dump = prog.dump_string("", "  ");
+        assert!(
+            dump.contains("FunctionCall undefined [UNRESOLVED]\n"),
+            "{}",
+            dump
+        );
+        assert!(dump.contains("FunctionCall f, resolved\n"), "{}", dump);
+    }
+
+    #[test]
+    fn clone_equals_original() {
+        let mut prog = parse_str("function f(){ println(1); }\nf();").unwrap();
+        RefResolver::resolve(&mut prog).unwrap();
+        let copy = prog.clone();
+        assert_eq!(copy, prog);
+        assert_eq!(copy.dump_string("", "  "), prog.dump_string("", "  "));
+
+        // 比较包括调用消解到的定义
+        let unresolved = parse_str("function f(){ println(1); }\nf();").unwrap();
+        assert_ne!(unresolved, prog);
     }
 }
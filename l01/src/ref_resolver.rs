@@ -1,48 +1,306 @@
-use crate::grammar::{FunctionDecl, Statement};
+use crate::analysis;
+use crate::builtins;
+use crate::diagnostics::Diagnostics;
+use crate::grammar::{Expression, Statement};
 use crate::prog::Prog;
 use crate::FunctionCall;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+// 一层作用域中声明的函数，值为函数的声明路径（见 FunctionCall::definition）与参数个数
+type Scope = HashMap<String, (Vec<usize>, usize)>;
 
 pub struct RefResolver {}
 impl RefResolver {
+    // 消解失败时返回第一个错误
     pub fn resolve(prog: &mut Prog) -> Result<(), String> {
-        let mut functions: HashMap<String, std::ptr::NonNull<FunctionDecl>> = HashMap::new();
+        RefResolver::resolve_with_diagnostics(prog, &mut Diagnostics::new())
+    }
+
+    // 与 resolve 相同，但 external 中的名称也是合法的函数，供嵌入解释器的宿主程序提供自己的函数
+    // 调用这些函数与调用内置函数一样不会设置 definition，由解释器交给宿主程序执行
+    pub fn resolve_with(prog: &mut Prog, external: &HashSet<String>) -> Result<(), String> {
+        RefResolver::resolve_all(prog, external, &mut Diagnostics::new())
+    }
+
+    // 与 resolve 相同，但会继续消解遇到错误之后的调用，把所有的错误与警告都记录到 diagnostics 中
+    // 只有错误会导致返回 Err，警告（如未使用的函数）不影响引用消解的结果
+    pub fn resolve_with_diagnostics(
+        prog: &mut Prog,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<(), String> {
+        RefResolver::resolve_all(prog, &HashSet::new(), diagnostics)
+    }
+
+    fn resolve_all(
+        prog: &mut Prog,
+        external: &HashSet<String>,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<(), String> {
+        let errors = diagnostics.errors.len();
+        let mut scopes = vec![RefResolver::declare(&prog.stmts, &[])];
+        RefResolver::resolve_statements(&mut scopes, &mut prog.stmts, &[], external, diagnostics);
+        if let Some(e) = diagnostics.errors.get(errors) {
+            return Err(e.to_string());
+        }
+
+        for decl in analysis::unused_function_decls(prog) {
+            diagnostics.warning(
+                format!("function {} is never used", decl.name),
+                decl.line,
+                decl.col,
+            );
+        }
+
+        Ok(())
+    }
 
-        for x in &mut prog.stmts {
+    // 收集一组语句中声明的函数，path 为这组语句所在函数的声明路径（顶层为空）
+    fn declare(stmts: &[Statement], path: &[usize]) -> Scope {
+        let mut scope = Scope::new();
+        for (i, x) in stmts.iter().enumerate() {
             if let Statement::FunctionDecl(decl) = x {
-                functions.insert(decl.name.to_string(), decl.into());
+                scope.insert(
+                    decl.name.to_string(),
+                    ([path, &[i]].concat(), decl.params.len()),
+                );
             }
         }
+        scope
+    }
 
-        for x in &mut prog.stmts {
+    fn resolve_statements(
+        scopes: &mut Vec<Scope>,
+        stmts: &mut [Statement],
+        path: &[usize],
+        external: &HashSet<String>,
+        diagnostics: &mut Diagnostics,
+    ) {
+        for (i, x) in stmts.iter_mut().enumerate() {
             match x {
                 Statement::FunctionDecl(decl) => {
-                    for call in &mut decl.body.stmts {
-                        RefResolver::resolve_function_call(&functions, call)?
-                    }
+                    // 函数体中声明的函数只在该函数体内可见
+                    let path = [path, &[i]].concat();
+                    scopes.push(RefResolver::declare(&decl.body.stmts, &path));
+                    RefResolver::resolve_statements(
+                        scopes,
+                        &mut decl.body.stmts,
+                        &path,
+                        external,
+                        diagnostics,
+                    );
+                    scopes.pop();
                 }
                 Statement::FunctionCall(call) => {
-                    RefResolver::resolve_function_call(&functions, call)?
+                    RefResolver::resolve_function_call(scopes, call, external, diagnostics)
+                }
+                Statement::VariableDecl(decl) => {
+                    if let Some(x) = &mut decl.init {
+                        RefResolver::resolve_expression(scopes, x, external, diagnostics)
+                    }
+                }
+                Statement::Return(stmt) => {
+                    if path.is_empty() {
+                        diagnostics.error(
+                            "return outside of function".to_string(),
+                            stmt.line,
+                            stmt.col,
+                        );
+                    }
+                    if let Some(x) = &mut stmt.value {
+                        RefResolver::resolve_expression(scopes, x, external, diagnostics)
+                    }
+                }
+                Statement::While(stmt) => {
+                    RefResolver::resolve_expression(
+                        scopes,
+                        &mut stmt.condition,
+                        external,
+                        diagnostics,
+                    );
+                    // 语句块中不能声明函数，沿用所在函数的作用域
+                    RefResolver::resolve_statements(
+                        scopes,
+                        &mut stmt.body.stmts,
+                        path,
+                        external,
+                        diagnostics,
+                    );
+                }
+                Statement::For(stmt) => {
+                    for x in stmt.init.iter_mut().chain(stmt.update.iter_mut()) {
+                        RefResolver::resolve_statements(
+                            scopes,
+                            std::slice::from_mut(x.as_mut()),
+                            path,
+                            external,
+                            diagnostics,
+                        );
+                    }
+                    if let Some(x) = &mut stmt.condition {
+                        RefResolver::resolve_expression(scopes, x, external, diagnostics);
+                    }
+                    RefResolver::resolve_statements(
+                        scopes,
+                        &mut stmt.body.stmts,
+                        path,
+                        external,
+                        diagnostics,
+                    );
+                }
+                Statement::Assignment(stmt) => {
+                    RefResolver::resolve_expression(scopes, &mut stmt.value, external, diagnostics)
+                }
+                // 被导入的函数已经由 resolve_imports 合并到程序的顶层
+                Statement::Import(_) | Statement::Empty(_) => {}
+                Statement::ExpressionStatement(expr, _) => {
+                    RefResolver::resolve_expression(scopes, expr, external, diagnostics)
                 }
             }
         }
-
-        Ok(())
     }
 
     fn resolve_function_call(
-        functions: &HashMap<String, std::ptr::NonNull<FunctionDecl>>,
+        scopes: &[Scope],
         call: &mut FunctionCall,
-    ) -> Result<(), String> {
-        match functions.get(&call.name) {
-            None => match call.name.as_ref() {
-                "println" => Ok(()),
-                _ => Err(format!("unkown function {}", call.name)),
-            },
-            Some(ptr) => {
-                call.definition = Some(ptr.clone());
-                Ok(())
+        external: &HashSet<String>,
+        diagnostics: &mut Diagnostics,
+    ) {
+        for x in &mut call.parameters {
+            RefResolver::resolve_expression(scopes, x, external, diagnostics)
+        }
+
+        // 从内层作用域向外查找
+        match scopes.iter().rev().find_map(|x| x.get(&*call.name)) {
+            None if builtins::is_builtin(&call.name) || external.contains(&*call.name) => {}
+            None => diagnostics.error(
+                format!("unkown function {}", call.name),
+                call.line,
+                call.col,
+            ),
+            Some((path, arity)) => {
+                // 内置函数的参数个数不固定，只检查自定义函数
+                if call.parameters.len() != *arity {
+                    diagnostics.error(
+                        format!(
+                            "function {} expects {} argument(s), got {}",
+                            call.name,
+                            arity,
+                            call.parameters.len()
+                        ),
+                        call.line,
+                        call.col,
+                    );
+                }
+                call.definition = Some(path.clone())
+            }
+        }
+    }
+
+    // 消解表达式中出现的函数调用
+    fn resolve_expression(
+        scopes: &[Scope],
+        expr: &mut Expression,
+        external: &HashSet<String>,
+        diagnostics: &mut Diagnostics,
+    ) {
+        match expr {
+            Expression::Call(call) => {
+                RefResolver::resolve_function_call(scopes, call, external, diagnostics)
+            }
+            Expression::Unary { operand, .. }
+            | Expression::Assign { value: operand, .. }
+            | Expression::Member {
+                target: operand, ..
+            } => RefResolver::resolve_expression(scopes, operand, external, diagnostics),
+            Expression::Binary { left, right, .. }
+            | Expression::Logical { left, right, .. }
+            | Expression::Index {
+                target: left,
+                index: right,
+                ..
+            } => {
+                RefResolver::resolve_expression(scopes, left, external, diagnostics);
+                RefResolver::resolve_expression(scopes, right, external, diagnostics)
             }
+            Expression::StringLiteral(_)
+            | Expression::IntegerLiteral(_)
+            | Expression::Char(_)
+            | Expression::Bool(_)
+            | Expression::Variable(_) => {}
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::parser::parse_str;
+
+    #[test]
+    fn recursive_call_resolves_to_itself() {
+        let mut prog = parse_str("function loop(){ loop(); } loop();").unwrap();
+        RefResolver::resolve(&mut prog).unwrap();
+        let calls: Vec<Option<Vec<usize>>> = prog.calls().map(|x| x.definition.clone()).collect();
+        assert_eq!(calls, vec![Some(vec![0]), Some(vec![0])]);
+
+        // 由调用深度的限制结束，而不是栈溢出
+        let e = Interpreter::with_max_call_depth(50)
+            .run_program(&prog, &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(e.message, "stack overflow: max call depth exceeded");
+    }
+
+    #[test]
+    fn unused_function_is_only_a_warning() {
+        let mut prog = parse_str("function unused(){}\nprintln(1);").unwrap();
+        let mut diagnostics = Diagnostics::new();
+        assert_eq!(
+            RefResolver::resolve_with_diagnostics(&mut prog, &mut diagnostics),
+            Ok(())
+        );
+        assert!(diagnostics.errors.is_empty());
+        let warnings: Vec<String> = diagnostics.warnings.iter().map(|x| x.to_string()).collect();
+        assert_eq!(warnings, vec!["function unused is never used at 1:1"]);
+    }
+
+    #[test]
+    fn inner_function_is_scoped_to_its_parent() {
+        let mut prog =
+            parse_str("function outer(){ function inner(){} inner(); } outer();").unwrap();
+        assert_eq!(RefResolver::resolve(&mut prog), Ok(()));
+
+        let mut prog = parse_str("function outer(){ function inner(){} }\ninner();").unwrap();
+        assert_eq!(
+            RefResolver::resolve(&mut prog),
+            Err("unkown function inner at 2:1".to_string())
+        );
+    }
+
+    #[test]
+    fn arity_is_checked_at_resolution() {
+        let mut prog = parse_str("function add(a, b){ return a + b; }\nadd(1);").unwrap();
+        assert_eq!(
+            RefResolver::resolve(&mut prog),
+            Err("function add expects 2 argument(s), got 1 at 2:1".to_string())
+        );
+
+        // 内置函数可以接受任意个参数
+        let mut prog = parse_str("println(1, 2, 3); println();").unwrap();
+        assert_eq!(RefResolver::resolve(&mut prog), Ok(()));
+    }
+
+    #[test]
+    fn external_functions_resolve_to_the_host() {
+        let external: HashSet<String> = vec!["hostFn".to_string()].into_iter().collect();
+        let mut prog = parse_str("hostFn(1);").unwrap();
+        assert_eq!(RefResolver::resolve_with(&mut prog, &external), Ok(()));
+        assert_eq!(prog.calls().next().unwrap().definition, None);
+
+        let mut prog = parse_str("hostFn(1);").unwrap();
+        assert_eq!(
+            RefResolver::resolve(&mut prog),
+            Err("unkown function hostFn at 1:1".to_string())
+        );
+    }
+}
@@ -1,17 +1,129 @@
-#[derive(Debug, PartialEq, Eq)]
+use crate::json::json_string;
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(unused)]
 pub enum TokenKind {
     Keyword,
     Identifier,
     StringLiteral,
+    CharLiteral, // 字符字面量，text 为转义后的单个字符
+    IntegerLiteral,
     Seperator,
     Operator,
+    Comment,    // 注释，text 为包含 // 或 /* */ 的原文，仅在词法分析器保留注释时产生
+    Whitespace, // 连续的空白符（包括换行），text 为其原文，仅在词法分析器保留空白符时产生
     EOF,
 }
 
+// 所有的关键字，词法分析器据此区分关键字与标识符
+pub const KEYWORDS: &[&str] = &[
+    "function", "let", "return", "while", "for", "import", "true", "false",
+];
+
+pub fn is_keyword(s: &str) -> bool {
+    KEYWORDS.contains(&s)
+}
+
 // 代表一个Token的数据结构
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Token {
     pub kind: TokenKind,
-    pub text: String,
+    pub text: Rc<str>, // 词法分析器输出的标识符、关键字、分隔符与运算符经过驻留，相同的 text 共享同一份内存
+    pub line: u64,     // Token 起始位置所在的行，从 1 开始
+    pub col: u64,      // Token 起始位置所在的列，从 1 开始
+    pub span: Option<(usize, usize)>, // Token 在源代码中的字节范围 [start, end)，没有源代码时为 None
+}
+
+impl Token {
+    // 是否为指定的关键字
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Keyword && &*self.text == keyword
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} '{}' at {}:{}",
+            self.kind, self.text, self.line, self.col
+        )
+    }
+}
+
+// 借用源代码的 Token
+// text 尽量直接引用源代码，以避免为每个 Token 分配内存；
+// 只有包含转义字符的字符串字面量才需要持有转义后的 text
+#[derive(Debug)]
+pub struct BorrowedToken<'a> {
+    pub kind: TokenKind,
+    pub text: Cow<'a, str>,
+    pub line: u64,
+    pub col: u64,
+}
+impl BorrowedToken<'_> {
+    pub fn into_owned(self) -> Token {
+        Token {
+            kind: self.kind,
+            text: self.text.into(),
+            line: self.line,
+            col: self.col,
+            span: None,
+        }
+    }
+}
+
+// 把 Token 串输出为 JSON 数组，每个 Token 为 {kind, text, line, col} 对象
+// 供编辑器等外部工具使用
+pub fn tokens_to_json(tokens: &[Token]) -> String {
+    let items: Vec<String> = tokens
+        .iter()
+        .map(|t| {
+            format!(
+                "{{\"kind\":{},\"text\":{},\"line\":{},\"col\":{}}}",
+                json_string(&format!("{:?}", t.kind)),
+                json_string(&t.text),
+                t.line,
+                t.col
+            )
+        })
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+
+    #[test]
+    fn tokens_as_json() {
+        assert_eq!(
+            tokens_to_json(&tokenize("function f").unwrap()),
+            concat!(
+                r#"[{"kind":"Keyword","text":"function","line":1,"col":1},"#,
+                r#"{"kind":"Identifier","text":"f","line":1,"col":10},"#,
+                r#"{"kind":"EOF","text":"","line":1,"col":11}]"#
+            )
+        );
+        assert_eq!(
+            tokens_to_json(&tokenize(r#""a\"b""#).unwrap()[..1]),
+            r#"[{"kind":"StringLiteral","text":"a\"b","line":1,"col":1}]"#
+        );
+    }
+
+    #[test]
+    fn keyword_set() {
+        assert!(is_keyword("function"));
+        assert!(!is_keyword("foo"));
+        // 词法分析器与关键字表一致
+        for x in KEYWORDS {
+            assert_eq!(tokenize(x).unwrap()[0].kind, TokenKind::Keyword, "{}", x);
+        }
+        assert!(tokenize("function").unwrap()[0].is_keyword("function"));
+        assert!(!tokenize("functions").unwrap()[0].is_keyword("function"));
+    }
 }
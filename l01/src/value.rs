@@ -0,0 +1,36 @@
+use std::fmt::{Display, Formatter};
+
+/**
+ * 运行时的值
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Char(char),
+    Str(String),
+}
+impl Value {
+    // 值的类型名称，即 typeof 的结果
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::Char(_) => "char",
+            Value::Str(_) => "string",
+        }
+    }
+}
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(x) => write!(f, "{}", x),
+            Value::Int(x) => write!(f, "{}", x),
+            Value::Char(x) => write!(f, "{}", x),
+            Value::Str(x) => write!(f, "{}", x),
+        }
+    }
+}
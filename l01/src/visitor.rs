@@ -0,0 +1,334 @@
+use crate::grammar::{
+    Assignment, Block, Expression, ForStatement, FunctionBody, FunctionCall, FunctionDecl,
+    ImportStatement, ReturnStatement, Statement, VariableDecl, WhileStatement,
+};
+use crate::prog::Prog;
+
+/**
+ * AST 的遍历器
+ * 默认实现会依次遍历所有的子节点，需要处理某类节点时覆盖对应的方法即可。
+ * 覆盖的方法如果还需要继续遍历子节点，可以调用同名的 walk_xxx 函数。
+ * 'ast 为 AST 的生命周期，遍历器可以保存遍历到的节点的引用。
+ */
+pub trait Visitor<'ast> {
+    fn visit_prog(&mut self, prog: &'ast Prog) {
+        walk_prog(self, prog)
+    }
+
+    fn visit_statement(&mut self, stmt: &'ast Statement) {
+        walk_statement(self, stmt)
+    }
+
+    fn visit_function_decl(&mut self, decl: &'ast FunctionDecl) {
+        walk_function_decl(self, decl)
+    }
+
+    fn visit_function_body(&mut self, body: &'ast FunctionBody) {
+        walk_function_body(self, body)
+    }
+
+    fn visit_function_call(&mut self, call: &'ast FunctionCall) {
+        walk_function_call(self, call)
+    }
+
+    fn visit_variable_decl(&mut self, decl: &'ast VariableDecl) {
+        walk_variable_decl(self, decl)
+    }
+
+    fn visit_return(&mut self, stmt: &'ast ReturnStatement) {
+        walk_return(self, stmt)
+    }
+
+    fn visit_while(&mut self, stmt: &'ast WhileStatement) {
+        walk_while(self, stmt)
+    }
+
+    fn visit_for(&mut self, stmt: &'ast ForStatement) {
+        walk_for(self, stmt)
+    }
+
+    fn visit_assignment(&mut self, stmt: &'ast Assignment) {
+        walk_assignment(self, stmt)
+    }
+
+    // import 语句没有子节点
+    fn visit_import(&mut self, _stmt: &'ast ImportStatement) {}
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_expression(&mut self, expr: &'ast Expression) {
+        walk_expression(self, expr)
+    }
+}
+
+pub fn walk_prog<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, prog: &'ast Prog) {
+    for x in &prog.stmts {
+        visitor.visit_statement(x);
+    }
+}
+
+pub fn walk_statement<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, stmt: &'ast Statement) {
+    match stmt {
+        Statement::FunctionDecl(x) => visitor.visit_function_decl(x),
+        Statement::FunctionCall(x) => visitor.visit_function_call(x),
+        Statement::VariableDecl(x) => visitor.visit_variable_decl(x),
+        Statement::Return(x) => visitor.visit_return(x),
+        Statement::While(x) => visitor.visit_while(x),
+        Statement::For(x) => visitor.visit_for(x),
+        Statement::Assignment(x) => visitor.visit_assignment(x),
+        Statement::Import(x) => visitor.visit_import(x),
+        Statement::ExpressionStatement(x, _) => visitor.visit_expression(x),
+        Statement::Empty(_) => {}
+    }
+}
+
+pub fn walk_function_decl<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    decl: &'ast FunctionDecl,
+) {
+    visitor.visit_function_body(&decl.body);
+}
+
+pub fn walk_function_body<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    body: &'ast FunctionBody,
+) {
+    for x in &body.stmts {
+        visitor.visit_statement(x);
+    }
+}
+
+pub fn walk_function_call<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    call: &'ast FunctionCall,
+) {
+    for x in &call.parameters {
+        visitor.visit_expression(x);
+    }
+}
+
+pub fn walk_variable_decl<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    decl: &'ast VariableDecl,
+) {
+    if let Some(x) = &decl.init {
+        visitor.visit_expression(x);
+    }
+}
+
+pub fn walk_return<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, stmt: &'ast ReturnStatement) {
+    if let Some(x) = &stmt.value {
+        visitor.visit_expression(x);
+    }
+}
+
+pub fn walk_while<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, stmt: &'ast WhileStatement) {
+    visitor.visit_expression(&stmt.condition);
+    visitor.visit_block(&stmt.body);
+}
+
+pub fn walk_for<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, stmt: &'ast ForStatement) {
+    if let Some(x) = &stmt.init {
+        visitor.visit_statement(x);
+    }
+    if let Some(x) = &stmt.condition {
+        visitor.visit_expression(x);
+    }
+    if let Some(x) = &stmt.update {
+        visitor.visit_statement(x);
+    }
+    visitor.visit_block(&stmt.body);
+}
+
+pub fn walk_assignment<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, stmt: &'ast Assignment) {
+    visitor.visit_expression(&stmt.value);
+}
+
+pub fn walk_block<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, block: &'ast Block) {
+    for x in &block.stmts {
+        visitor.visit_statement(x);
+    }
+}
+
+pub fn walk_expression<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, expr: &'ast Expression) {
+    match expr {
+        Expression::Call(call) => visitor.visit_function_call(call),
+        Expression::Unary { operand, .. } => visitor.visit_expression(operand),
+        Expression::Assign { value, .. } => visitor.visit_expression(value),
+        Expression::Member { target, .. } => visitor.visit_expression(target),
+        Expression::Binary { left, right, .. }
+        | Expression::Logical { left, right, .. }
+        | Expression::Index {
+            target: left,
+            index: right,
+            ..
+        } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Expression::StringLiteral(_)
+        | Expression::IntegerLiteral(_)
+        | Expression::Char(_)
+        | Expression::Bool(_)
+        | Expression::Variable(_) => {}
+    }
+}
+
+/**
+ * 可以修改 AST 的遍历器，用于变换 AST 的优化等过程
+ * 与 Visitor 相同，默认实现会依次遍历所有的子节点
+ */
+pub trait VisitorMut {
+    fn visit_prog_mut(&mut self, prog: &mut Prog) {
+        walk_prog_mut(self, prog)
+    }
+
+    fn visit_statement_mut(&mut self, stmt: &mut Statement) {
+        walk_statement_mut(self, stmt)
+    }
+
+    fn visit_function_decl_mut(&mut self, decl: &mut FunctionDecl) {
+        walk_function_decl_mut(self, decl)
+    }
+
+    fn visit_function_body_mut(&mut self, body: &mut FunctionBody) {
+        walk_function_body_mut(self, body)
+    }
+
+    fn visit_function_call_mut(&mut self, call: &mut FunctionCall) {
+        walk_function_call_mut(self, call)
+    }
+
+    fn visit_variable_decl_mut(&mut self, decl: &mut VariableDecl) {
+        walk_variable_decl_mut(self, decl)
+    }
+
+    fn visit_return_mut(&mut self, stmt: &mut ReturnStatement) {
+        walk_return_mut(self, stmt)
+    }
+
+    fn visit_while_mut(&mut self, stmt: &mut WhileStatement) {
+        walk_while_mut(self, stmt)
+    }
+
+    fn visit_for_mut(&mut self, stmt: &mut ForStatement) {
+        walk_for_mut(self, stmt)
+    }
+
+    fn visit_assignment_mut(&mut self, stmt: &mut Assignment) {
+        walk_assignment_mut(self, stmt)
+    }
+
+    fn visit_import_mut(&mut self, _stmt: &mut ImportStatement) {}
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        walk_expression_mut(self, expr)
+    }
+}
+
+pub fn walk_prog_mut<V: VisitorMut + ?Sized>(visitor: &mut V, prog: &mut Prog) {
+    for x in &mut prog.stmts {
+        visitor.visit_statement_mut(x);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Statement) {
+    match stmt {
+        Statement::FunctionDecl(x) => visitor.visit_function_decl_mut(x),
+        Statement::FunctionCall(x) => visitor.visit_function_call_mut(x),
+        Statement::VariableDecl(x) => visitor.visit_variable_decl_mut(x),
+        Statement::Return(x) => visitor.visit_return_mut(x),
+        Statement::While(x) => visitor.visit_while_mut(x),
+        Statement::For(x) => visitor.visit_for_mut(x),
+        Statement::Assignment(x) => visitor.visit_assignment_mut(x),
+        Statement::Import(x) => visitor.visit_import_mut(x),
+        Statement::ExpressionStatement(x, _) => visitor.visit_expression_mut(x),
+        Statement::Empty(_) => {}
+    }
+}
+
+pub fn walk_function_decl_mut<V: VisitorMut + ?Sized>(visitor: &mut V, decl: &mut FunctionDecl) {
+    visitor.visit_function_body_mut(&mut decl.body);
+}
+
+pub fn walk_function_body_mut<V: VisitorMut + ?Sized>(visitor: &mut V, body: &mut FunctionBody) {
+    for x in &mut body.stmts {
+        visitor.visit_statement_mut(x);
+    }
+}
+
+pub fn walk_function_call_mut<V: VisitorMut + ?Sized>(visitor: &mut V, call: &mut FunctionCall) {
+    for x in &mut call.parameters {
+        visitor.visit_expression_mut(x);
+    }
+}
+
+pub fn walk_variable_decl_mut<V: VisitorMut + ?Sized>(visitor: &mut V, decl: &mut VariableDecl) {
+    if let Some(x) = &mut decl.init {
+        visitor.visit_expression_mut(x);
+    }
+}
+
+pub fn walk_return_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut ReturnStatement) {
+    if let Some(x) = &mut stmt.value {
+        visitor.visit_expression_mut(x);
+    }
+}
+
+pub fn walk_while_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut WhileStatement) {
+    visitor.visit_expression_mut(&mut stmt.condition);
+    visitor.visit_block_mut(&mut stmt.body);
+}
+
+pub fn walk_for_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut ForStatement) {
+    if let Some(x) = &mut stmt.init {
+        visitor.visit_statement_mut(x);
+    }
+    if let Some(x) = &mut stmt.condition {
+        visitor.visit_expression_mut(x);
+    }
+    if let Some(x) = &mut stmt.update {
+        visitor.visit_statement_mut(x);
+    }
+    visitor.visit_block_mut(&mut stmt.body);
+}
+
+pub fn walk_assignment_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Assignment) {
+    visitor.visit_expression_mut(&mut stmt.value);
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    for x in &mut block.stmts {
+        visitor.visit_statement_mut(x);
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expression) {
+    match expr {
+        Expression::Call(call) => visitor.visit_function_call_mut(call),
+        Expression::Unary { operand, .. } => visitor.visit_expression_mut(operand),
+        Expression::Assign { value, .. } => visitor.visit_expression_mut(value),
+        Expression::Member { target, .. } => visitor.visit_expression_mut(target),
+        Expression::Binary { left, right, .. }
+        | Expression::Logical { left, right, .. }
+        | Expression::Index {
+            target: left,
+            index: right,
+            ..
+        } => {
+            visitor.visit_expression_mut(left);
+            visitor.visit_expression_mut(right);
+        }
+        Expression::StringLiteral(_)
+        | Expression::IntegerLiteral(_)
+        | Expression::Char(_)
+        | Expression::Bool(_)
+        | Expression::Variable(_) => {}
+    }
+}
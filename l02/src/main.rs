@@ -1,505 +1,550 @@
-use std::iter::Peekable;
-use std::str::Chars;
+use std::io::{self, IsTerminal, Read, Write};
+use std::{env, fs, process, thread};
 
-/**
+/*
  * 第2节
  * 本节的知识点有两个：
  * 1.学会词法分析；
  * 2.升级语法分析为LL算法，因此需要知道如何使用First和Follow集合。
  *
- * 本节采用的词法规则是比较精简的，比如不考虑Unicode。
- * Identifier: [a-zA-Z_][a-zA-Z0-9_]* ;
+ * 本节采用的词法规则是比较精简的，但标识符支持Unicode（按照 UAX #31 的 XID_Start / XID_Continue）。
+ * Identifier: (XID_Start | '_') XID_Continue* ;
  */
 
 /////////////////////////////////////////////////////////////////////////
-// 数据流定义
-
-struct CharStream<'a> {
-    data: Peekable<Chars<'a>>,
-    line: u64,
-    col: u64,
-}
-impl CharStream<'_> {
-    fn new(data: &str) -> CharStream {
-        CharStream {
-            data: data.chars().peekable(),
-            line: 1,
-            col: 0,
-        }
-    }
+// 词法分析
+// 词法分析器位于 l01 库的 lexer 模块中，以便嵌入到其他程序（如服务端或模糊测试）中使用
 
-    fn line(&self) -> u64 {
-        self.line
-    }
+use l01::{tokenize, tokens_to_json, Token, TokenBuffer};
 
-    fn col(&self) -> u64 {
-        self.col
+/////////////////////////////////////////////////////////////////////////
+// 语法分析
+// 语法解析程序位于 l01 库中，这里只需要把词法分析器包装成可回溯的 Token 流
+
+use l01::{parse_str, Dumper, Parser};
+
+// 把 Token 串格式化为文本，每行一个 Token：位置、种类、原文，各列对齐
+fn format_tokens(tokens: &[Token]) -> String {
+    let positions: Vec<String> = tokens
+        .iter()
+        .map(|x| format!("{}:{}", x.line, x.col))
+        .collect();
+    let kinds: Vec<String> = tokens.iter().map(|x| format!("{:?}", x.kind)).collect();
+    let position_width = positions.iter().map(|x| x.len()).max().unwrap_or(0);
+    let kind_width = kinds.iter().map(|x| x.len()).max().unwrap_or(0);
+
+    let mut s = String::new();
+    for ((position, kind), token) in positions.iter().zip(&kinds).zip(tokens) {
+        s += &format!(
+            "{:<pw$}  {:<kw$}  {:?}\n",
+            position,
+            kind,
+            token.text,
+            pw = position_width,
+            kw = kind_width
+        );
     }
+    s
+}
 
-    fn peek(&mut self) -> Option<&char> {
-        self.data.peek()
-    }
+// 对源代码做词法分析和语法分析
+fn parse(code: &str) -> Result<Prog, String> {
+    parse_str(code).map_err(|e| e.to_string())
 }
-impl Iterator for CharStream<'_> {
-    type Item = char;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let ch = self.data.next();
-        if let Some(ch) = ch {
-            if ch == '\n' {
-                self.line += 1;
-                self.col = 0;
-            } else {
-                self.col += 1;
-            }
-        }
-        ch
-    }
+
+fn parse_tokens(tokens: Vec<Token>) -> Result<Prog, String> {
+    Parser::new(TokenBuffer::new(tokens.into_iter()))
+        .parse_prog()
+        .map_err(|e| e.to_string())
 }
 
 /////////////////////////////////////////////////////////////////////////
-// 词法分析
-// 当前支持
-// - Identifier, keyword
-// - Seperator '(' | ')' | '{' | '}' | ';' | ','
-// - StringLiteral
-// - Comment (single and block)
-// - Operator '/' | '/=' | '+' | '++' | '+=' | '-' | '--' | '-='
-// 尚未支持
-// - 数字字面量
-
-use l01::{Token, TokenKind};
-
-struct Tokenizer<'a> {
-    stream: CharStream<'a>,
-    eof: bool,
+// 语义分析
+use l01::interpreter::STACK_SIZE;
+use l01::{
+    dead_code, fold_constants, resolve_imports, Diagnostic, Diagnostics, Environment, Interpreter,
+    Prog, RefResolver, Value,
+};
+
+// 读取并解析 import 语句引用的文件，path 已经由 resolve_imports 换算为相对于当前目录的路径
+// 被导入文件中的错误注明所在的文件，以免被当作主程序中的位置
+fn load_module(path: &str) -> Result<Prog, String> {
+    let code = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    parse(&code).map_err(|e| format!("{} in {}", e, path))
 }
-impl Tokenizer<'_> {
-    fn new(code: &str) -> Peekable<Tokenizer> {
-        return Tokenizer {
-            stream: CharStream::new(code),
-            eof: false,
-        }
-        .peekable();
-    }
 
-    #[allow(dead_code)]
-    fn from_stream(stream: CharStream) -> Peekable<Tokenizer> {
-        return Tokenizer { stream, eof: false }.peekable();
+/////////////////////////////////////////////////////////////////////////
+// 主程序
+// file 为程序所在文件的路径，import 语句中的路径相对于这个文件
+fn compile_and_run(code: &str, file: &str, interpreter: Interpreter) -> Result<(), String> {
+    // 词法分析
+    let tokens = tokenize(code).map_err(|e| e.to_string())?;
+    println!("\n词法分析后的Token:");
+    print!("{}", format_tokens(&tokens));
+
+    // 语法分析
+    let mut prog = parse_tokens(tokens)?;
+    println!("\n语法分析后的AST:");
+    prog.dump("");
+
+    // 语义分析
+    resolve_imports(&mut prog, file, &mut load_module).map_err(|e| e.to_string())?;
+    let mut diagnostics = Diagnostics::new();
+    dead_code(&mut prog, &mut diagnostics, true);
+    RefResolver::resolve_with_diagnostics(&mut prog, &mut diagnostics)?;
+    for x in &diagnostics.warnings {
+        eprintln!("warning: {}", x);
     }
 
-    // 从字符串流中获取一个新Token
-    fn next_token(&mut self) -> Option<Token> {
-        if self.eof {
-            return None;
-        }
+    // 常量折叠
+    fold_constants(&mut prog);
+    println!("\n语义分析后的AST:");
+    prog.dump("");
 
-        // 忽略所有的空白符
-        self.skip_whitespaces();
+    // 运行程序
+    println!("\n运行程序");
+    interpreter.run(&prog)
+}
 
-        match self.stream.peek() {
-            None => {
-                self.eof = true;
-                Some(Token {
-                    kind: TokenKind::EOF,
-                    text: "".to_string(),
-                })
-            }
-            Some(&ch) => {
-                match ch {
-                    '"' => return Some(self.parse_string_literal().unwrap()),
-                    '(' | ')' | '{' | '}' | ';' | ',' => {
-                        return Some(Token {
-                            kind: TokenKind::Seperator,
-                            text: self.stream.next().unwrap().to_string(),
-                        })
-                    }
-                    '+' => {
-                        // 可能是 +, ++, +=
-                        self.stream.next();
-
-                        return match self.stream.peek() {
-                            Some('+') => Some(Token {
-                                kind: TokenKind::Operator,
-                                text: "++".to_string(),
-                            }),
-                            Some('=') => Some(Token {
-                                kind: TokenKind::Operator,
-                                text: "+=".to_string(),
-                            }),
-                            _ => Some(Token {
-                                kind: TokenKind::Operator,
-                                text: "+".to_string(),
-                            }),
-                        };
-                    }
-                    '-' => {
-                        // 可能是 -, --, -=
-                        self.stream.next();
-
-                        return match self.stream.peek() {
-                            Some('-') => Some(Token {
-                                kind: TokenKind::Operator,
-                                text: "--".to_string(),
-                            }),
-                            Some('=') => Some(Token {
-                                kind: TokenKind::Operator,
-                                text: "-=".to_string(),
-                            }),
-                            _ => Some(Token {
-                                kind: TokenKind::Operator,
-                                text: "-".to_string(),
-                            }),
-                        };
-                    }
-                    '*' => {
-                        // 可能是 *, *=
-                        self.stream.next();
-
-                        return match self.stream.peek() {
-                            Some('=') => Some(Token {
-                                kind: TokenKind::Operator,
-                                text: "*=".to_string(),
-                            }),
-                            _ => Some(Token {
-                                kind: TokenKind::Operator,
-                                text: "*".to_string(),
-                            }),
-                        };
-                    }
-                    '/' => {
-                        // 可能是 /*, //, /, /=
-                        self.stream.next();
-
-                        return match self.stream.peek() {
-                            Some('/') => {
-                                self.skip_line();
-                                self.next_token()
-                            }
-                            Some('*') => {
-                                self.skip_block_comment().unwrap();
-                                self.next_token()
-                            }
-                            Some('=') => Some(Token {
-                                kind: TokenKind::Operator,
-                                text: "/=".to_string(),
-                            }),
-                            _ => Some(Token {
-                                kind: TokenKind::Operator,
-                                text: "/".to_string(),
-                            }),
-                        };
-                    }
-                    _ => {}
-                }
-
-                if ch.is_alphabetic() {
-                    return Some(self.parse_identifier());
-                }
-                if ch == '/' {}
-
-                // 无法识别，作为 identifier
-                panic!(
-                    "Invalid token {} at {}:{}",
-                    ch,
-                    self.stream.line(),
-                    self.stream.col()
-                )
-            }
-        }
+// 检查代码但不执行，每行输出一个错误或警告
+// 存在错误时返回 Err
+fn check(code: &str, file: &str) -> Result<(), String> {
+    let diagnostics = diagnose(code, file, &mut load_module)?;
+    for x in &diagnostics.warnings {
+        println!("warning: {}", x);
+    }
+    for x in &diagnostics.errors {
+        println!("error: {}", x);
     }
 
-    fn skip_whitespaces(&mut self) {
-        while matches!(self.stream.peek(), Some(c) if c.is_whitespace()) {
-            self.stream.next();
-        }
+    if diagnostics.has_errors() {
+        return Err(format!("{} error(s) found", diagnostics.errors.len()));
     }
+    Ok(())
+}
 
-    // 跳过整行，在解析到 // 后使用
-    fn skip_line(&mut self) {
-        while matches!(self.stream.peek(), Some(&c) if c != '\n') {
-            self.stream.next();
-        }
+// 收集语法分析、导入与语义分析发现的所有问题，合并后按位置排序
+// 只有词法错误会中止检查
+fn diagnose(
+    code: &str,
+    file: &str,
+    load: &mut dyn FnMut(&str) -> Result<Prog, String>,
+) -> Result<Diagnostics, String> {
+    let tokens = tokenize(code).map_err(|e| format!("error: {}", e))?;
+
+    // 语法错误不会中止检查，跳过出错的语句后继续，以便一次报告所有错误
+    let (mut prog, parse_errors) =
+        Parser::new(TokenBuffer::new(tokens.into_iter())).parse_prog_recovering();
+    let mut diagnostics = Diagnostics::new();
+    diagnostics.errors.extend(parse_errors);
+
+    if let Err(e) = resolve_imports(&mut prog, file, load) {
+        diagnostics.errors.push(e);
     }
 
-    // 跳过段注释
-    // 如果一直到 EOF 都没有读到 */ 则返回错误
-    fn skip_block_comment(&mut self) -> Result<(), String> {
-        self.stream.next();
-
-        while let Some(&c) = self.stream.peek() {
-            self.stream.next();
-
-            if c == '*' {
-                if let Some(&c) = self.stream.peek() {
-                    if c == '/' {
-                        return Ok(());
-                    }
-                } else {
-                    break; // will return error
-                }
-            }
-        }
+    dead_code(&mut prog, &mut diagnostics, false);
+    let _ = RefResolver::resolve_with_diagnostics(&mut prog, &mut diagnostics);
+    diagnostics.sort_by_position();
+    Ok(diagnostics)
+}
 
-        return Err("No */ found until EOF".to_string());
+// 交互式解释器
+// 每次读入一行代码，追加到同一个 Prog 中执行，之前声明的函数与变量在之后的输入中仍然可以使用
+struct Repl {
+    prog: Prog,
+    interpreter: Interpreter,
+    env: Environment,
+}
+impl Repl {
+    fn new() -> Repl {
+        Repl {
+            prog: Prog::new(Vec::new()),
+            interpreter: Interpreter::new(),
+            env: Environment::new(),
+        }
     }
 
-    // identifier 为以字母开头，后接若干数字/字符串/下划线
-    fn parse_identifier(&mut self) -> Token {
-        let mut text: String = self.stream.next().unwrap().into(); // 由上层调用保证当前是一个合法的 identifier 开头
-
-        while matches!(self.stream.peek(), Some(x) if Tokenizer::is_identifier_char(x)) {
-            text.push(self.stream.next().unwrap());
+    // 执行一行代码，返回其中最后一个顶层语句的值
+    fn eval(&mut self, line: &str) -> Result<Value, String> {
+        let new_prog = parse(line)?;
+
+        let start = self.prog.stmts.len();
+        self.prog.stmts.extend(new_prog.stmts);
+        if let Err(e) = RefResolver::resolve(&mut self.prog) {
+            // 撤销本次输入，并恢复之前的引用消解结果
+            self.prog.stmts.truncate(start);
+            RefResolver::resolve(&mut self.prog)?;
+            return Err(e);
         }
 
-        match text.as_ref() {
-            "function" => Token {
-                kind: TokenKind::Keyword,
-                text: text.to_string(),
-            },
-            _ => Token {
-                kind: TokenKind::Identifier,
-                text: text.to_string(),
-            },
+        let mut value = Value::Null;
+        for x in &self.prog.stmts[start..] {
+            value = self
+                .interpreter
+                .run_statement(&self.prog, x, &mut self.env)?;
         }
+        Ok(value)
     }
 
-    fn is_identifier_char(c: &char) -> bool {
-        *c == '_' || c.is_alphanumeric()
-    }
+    fn run(&mut self) -> Result<(), String> {
+        let mut line = String::new();
+        loop {
+            print!("> ");
+            io::stdout().flush().map_err(|e| e.to_string())?;
+
+            line.clear();
+            if io::stdin()
+                .read_line(&mut line)
+                .map_err(|e| e.to_string())?
+                == 0
+            {
+                println!();
+                return Ok(());
+            }
 
-    // 字符串字面量，表现为 "xxx"
-    // 当引号未闭合时返回 error
-    fn parse_string_literal(&mut self) -> Result<Token, String> {
-        self.stream.next(); // 忽略起始引号
-        let mut text = String::new();
-
-        while let Some(x) = self.stream.peek() {
-            match x {
-                '\n' => {
-                    return Err(format!(
-                        "Unexpected line break at {}:{}",
-                        self.stream.line(),
-                        self.stream.col()
-                    ))
-                }
-                '\\' => {
-                    self.stream.next();
-                    match self.stream.peek() {
-                        Some('n') => {
-                            self.stream.next();
-                            text.push('\n');
-                        }
-                        Some('\\') => {
-                            self.stream.next();
-                            text.push('\\');
-                        }
-                        _ => {
-                            return Err(format!(
-                                "Unexpected {} at {}:{}",
-                                '\\',
-                                self.stream.line(),
-                                self.stream.col()
-                            ))
-                        }
-                    }
-                }
-                '"' => {
-                    self.stream.next();
-                    return Ok(Token {
-                        kind: TokenKind::StringLiteral,
-                        text,
-                    });
-                }
-                _ => text.push(self.stream.next().unwrap()),
+            match self.eval(&line) {
+                Ok(Value::Null) => {}
+                Ok(value) => println!("{}", value),
+                Err(e) => eprintln!("{}", e),
             }
         }
-
-        Err(format!(
-            "Expecting {} at {}:{}",
-            "\"",
-            self.stream.line(),
-            self.stream.col()
-        ))
     }
 }
-impl Iterator for Tokenizer<'_> {
-    type Item = Token;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.next_token()
+const DEFAULT_CODE: &str = include_str!("default.ps");
+
+// 读取源代码
+// path 为 "-"，或未指定 path 且标准输入不是终端时，从标准输入读取
+// 未指定 path 且标准输入是终端时，使用内置的示例代码
+fn read_code(path: Option<&str>) -> Result<String, String> {
+    match path {
+        Some("-") => read_stdin(),
+        Some(path) => fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e)),
+        None if !io::stdin().is_terminal() => read_stdin(),
+        None => Ok(DEFAULT_CODE.to_string()),
     }
 }
 
-/////////////////////////////////////////////////////////////////////////
-// 语法分析
-// 包括了AST的数据结构和递归下降的语法解析程序
-
-use l01::{Dumper, FunctionBody, FunctionCall, FunctionDecl, Prog, Statement};
+fn read_stdin() -> Result<String, String> {
+    read_all(io::stdin())
+}
 
-struct Parser<'a> {
-    tokenizer: Peekable<Tokenizer<'a>>,
+// 读取 reader 中的全部内容作为源代码
+fn read_all(mut reader: impl Read) -> Result<String, String> {
+    let mut code = String::new();
+    reader
+        .read_to_string(&mut code)
+        .map_err(|e| format!("Cannot read stdin: {}", e))?;
+    Ok(code)
 }
-impl Parser<'_> {
-    fn new(tokenizer: Peekable<Tokenizer>) -> Parser {
-        Parser { tokenizer }
-    }
-    fn parse_prog(mut self) -> Result<Prog, String> {
-        let mut stmts: Vec<Statement> = Vec::new();
 
-        while let Some(token) = self.tokenizer.peek() {
-            if token.kind == TokenKind::EOF {
-                break;
-            };
+// 把错误信息渲染为便于阅读的形式：文件名与位置、错误信息，以及出错的那行代码和指向出错列的 ^
+// 错误信息以 " at 行:列" 结尾时才能确定位置，否则只输出文件名与错误信息
+// color 为 true 时使用 ANSI 颜色：位置为粗体，error 与 ^ 为红色
+fn render_error(message: &str, code: &str, file: &str, color: bool) -> String {
+    let (bold, red, reset) = if color {
+        ("\x1b[1m", "\x1b[1;31m", "\x1b[0m")
+    } else {
+        ("", "", "")
+    };
+
+    // 取第一个 " at 行:列" 作为位置，其后的位置（如 "opened at 1:14"）属于信息的一部分
+    let (message, line, col) = match Diagnostic::from_message(message) {
+        Some(x) if x.line > 0 && x.col > 0 => (x.message, x.line as usize, x.col as usize),
+        _ => {
+            return format!(
+                "{}{}:{} {}error:{} {}",
+                bold, file, reset, red, reset, message
+            )
+        }
+    };
+
+    let mut s = format!(
+        "{}{}:{}:{}:{} {}error:{} {}",
+        bold, file, line, col, reset, red, reset, message
+    );
+    if let Some(source) = code.lines().nth(line - 1) {
+        // ^ 前面的填充与源代码一致地使用制表符，以便在任意制表符宽度下都能对齐
+        let padding: String = source
+            .chars()
+            .take(col - 1)
+            .map(|x| if x == '\t' { '\t' } else { ' ' })
+            .collect();
+        s += &format!("\n{}\n{}{}^{}", source, padding, red, reset);
+    }
+    s
+}
 
-            if token.kind == TokenKind::Keyword && token.text == "function" {
-                stmts.push(Statement::FunctionDecl(self.parse_function_decl()?));
-                continue;
-            }
-            if token.kind == TokenKind::Identifier {
-                stmts.push(Statement::FunctionCall(self.parse_function_call()?));
-                continue;
+// 命令行参数
+// 用法: l02 [--tokens-json | --emit-tokens | --dump-ast | --check | --repl] [--main] [--no-color]
+//           [源文件路径 | -]
+#[derive(Default)]
+struct Options {
+    tokens_json: bool,    // 只做词法分析，并把 Token 串以 JSON 输出
+    emit_tokens: bool,    // 只做词法分析，并把 Token 串以对齐的文本输出
+    dump_ast: bool,       // 只做语法分析，并输出 AST，不执行
+    check: bool,          // 只做词法、语法分析与引用消解，报告所有问题，不执行
+    repl: bool,           // 交互式执行
+    entry_point: bool,    // 程序声明了 main 时只执行 main()
+    no_color: bool,       // 错误信息不使用颜色，标准错误不是终端时也不使用颜色
+    path: Option<String>, // 源文件路径
+}
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options::default();
+        for arg in args {
+            match arg.as_ref() {
+                "--tokens-json" => options.tokens_json = true,
+                "--emit-tokens" => options.emit_tokens = true,
+                "--dump-ast" => options.dump_ast = true,
+                "--check" => options.check = true,
+                "--repl" => options.repl = true,
+                "--main" => options.entry_point = true,
+                "--no-color" => options.no_color = true,
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ if options.path.is_none() => options.path = Some(arg),
+                _ => return Err(format!("Unexpected argument {}", arg)),
             }
-
-            return Err("unknown statement".into());
         }
+        Ok(options)
+    }
+}
 
-        Ok(Prog::new(stmts))
+fn run(options: &Options) -> Result<(), String> {
+    if options.repl {
+        return Repl::new().run();
     }
 
-    // 解析函数声明
-    // 语法规则：
-    // functionDecl: "function" Identifier "(" ")"  functionBody;
-    fn parse_function_decl(&mut self) -> Result<FunctionDecl, String> {
-        self.tokenizer.next(); // Keyword "function"
+    let code = read_code(options.path.as_deref())?;
+    // 源文件的名称，用于显示错误；import 语句中的路径相对于源文件，从标准输入读取时相对于当前目录
+    let file = match options.path.as_deref() {
+        Some("-") => "<stdin>",
+        Some(path) => path,
+        None if !io::stdin().is_terminal() => "<stdin>",
+        None => "<default>",
+    };
+
+    if options.tokens_json {
+        let tokens = tokenize(&code).map_err(|e| e.to_string())?;
+        println!("{}", tokens_to_json(&tokens));
+        return Ok(());
+    }
 
-        let t = self.tokenizer.next().ok_or("invalid token".to_string())?; // Identifier
-        if t.kind != TokenKind::Identifier {
-            return Err(format!("expect Identifier but got {:?}", t));
-        }
-        let function_name = t.text.to_string();
+    if options.emit_tokens {
+        let tokens = tokenize(&code).map_err(|e| e.to_string())?;
+        print!("{}", format_tokens(&tokens));
+        return Ok(());
+    }
 
-        // "(",
-        let t = self.tokenizer.next().ok_or("invalid token".to_string())?;
-        if t.kind != TokenKind::Seperator || t.text != "(" {
-            return Err(format!("expect Seperator '(' but got {:?}", t));
-        }
-        // 暂时不支持参数
-        // ")"
-        let t = self.tokenizer.next().ok_or("invalid token".to_string())?;
-        if t.kind != TokenKind::Seperator || t.text != ")" {
-            return Err(format!("expect Seperator ')' but got {:?}", t));
-        }
+    if options.check {
+        return check(&code, file);
+    }
 
-        // 解析函数体
-        let function_body = self.parse_function_body()?;
+    let result = if options.dump_ast {
+        parse(&code).map(|prog| print!("{}", prog.dump_string("", "  ")))
+    } else {
+        compile_and_run(
+            &code,
+            file,
+            Interpreter::new().use_entry_point(options.entry_point),
+        )
+    };
+
+    // 编译或运行出错时，在错误信息下方显示出错的代码
+    let color = !options.no_color && io::stderr().is_terminal();
+    result.map_err(|e| render_error(&e, &code, file, color))
+}
 
-        // 解析成功
-        return Ok(FunctionDecl::new(function_name, function_body));
+fn main() {
+    // 在足够大的栈上执行，使递归过深的程序报告调用深度超限，而不是让进程栈溢出
+    let handle = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| Options::parse(env::args().skip(1)).and_then(|options| run(&options)))
+        .unwrap_or_else(|e| {
+            eprintln!("Cannot start the interpreter: {}", e);
+            process::exit(1);
+        });
+    // 线程 panic 时已经输出了错误信息
+    let result = handle.join().unwrap_or_else(|_| process::exit(101));
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
     }
+}
 
-    // 解析函数体
-    // 语法规则：
-    // functionBody : '{' functionCall* '}' ;
-    fn parse_function_body(&mut self) -> Result<FunctionBody, String> {
-        let t = self.tokenizer.next().ok_or("invalid token".to_string())?;
-        if t.kind != TokenKind::Seperator || t.text != "{" {
-            return Err(format!("expect Seperator '{}' but got {:?}", '{', t));
-        }
-
-        let mut stmts = Vec::new();
-        loop {
-            if let Some(token) = self.tokenizer.peek() {
-                if token.kind == TokenKind::Identifier {
-                    stmts.push(self.parse_function_call()?);
-                    continue;
-                }
-                if token.kind == TokenKind::Seperator && token.text == "}" {
-                    self.tokenizer.next();
-                    return Ok(FunctionBody::new(stmts));
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn check_sorts_all_diagnostics_by_position() {
+        let code = "undefined();\nimport \"missing.ps\";\nlet x = ;\n";
+        let diagnostics = diagnose(code, "main.ps", &mut |path| {
+            Err(format!("no such file {}", path))
+        })
+        .unwrap();
+        let errors: Vec<String> = diagnostics.errors.iter().map(|x| x.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "unkown function undefined at 1:1",
+                "cannot import missing.ps: no such file missing.ps at 2:1",
+                "expect expression but got Seperator ';' at 3:9",
+            ]
+        );
+    }
 
-            return Err(format!("expect Seperator '{}' but got {:?}", '}', t).into());
-        }
+    #[test]
+    fn tokens_are_printed_and_parsed_from_one_pass() {
+        let code = "function f(x){ println(x); }\nf(\"a\");";
+        let tokens = tokenize(code).unwrap();
+        assert_eq!(
+            format_tokens(&tokens[..3]),
+            "1:1   Keyword     \"function\"\n1:10  Identifier  \"f\"\n1:11  Seperator   \"(\"\n"
+        );
+        assert_eq!(parse_tokens(tokens).unwrap(), parse(code).unwrap());
     }
 
-    // 解析函数调用
-    // functionCall : Identifier '(' parameter* ')' ;
-    fn parse_function_call(&mut self) -> Result<FunctionCall, String> {
-        let function_name = self.tokenizer.next().unwrap().text;
+    // 在临时目录中写入一个源文件，返回其路径
+    fn temp_file(name: &str, code: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("l02-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, code).unwrap();
+        path
+    }
 
-        let t = self.tokenizer.next().ok_or("invalid token".to_string())?;
-        if t.kind != TokenKind::Seperator || t.text != "(" {
-            return Err(format!("expect Seperator '{}' but got {:?}", '(', t));
-        }
+    // 以命令行参数 args 运行
+    fn run_args(args: &[&str]) -> Result<(), String> {
+        let options = Options::parse(args.iter().map(|x| x.to_string()))?;
+        run(&options)
+    }
 
-        // function call
-        let mut function_parameters = Vec::new();
-        // parameter, parameter, ... )
-        let mut t = self.tokenizer.next().ok_or("invalid token".to_string())?;
-        while t.kind != TokenKind::Seperator || t.text != ")" {
-            // t should be StringLiteral
-            if t.kind != TokenKind::StringLiteral {
-                return Err(format!("expect string parameter '(' but got {:?}", t).into());
-            }
-            function_parameters.push(t.text.to_string());
+    #[test]
+    fn runs_source_file_argument() {
+        let path = temp_file("ok.ps", "function f(){ println(\"hi\"); }\nf();");
+        assert_eq!(run_args(&[path.to_str().unwrap()]), Ok(()));
+
+        let path = temp_file("fail.ps", "println(1 / 0);");
+        let path = path.to_str().unwrap();
+        let e = run_args(&["--no-color", path]).unwrap_err();
+        assert_eq!(e, format!("{}: error: Division by zero", path));
+
+        // 错误信息中有多个位置时，以第一个位置为准
+        let path = temp_file("unclosed.ps", "function f(){\n  println(1);\n");
+        let path = path.to_str().unwrap();
+        let e = run_args(&["--no-color", path]).unwrap_err();
+        assert_eq!(
+            e,
+            format!(
+                "{}:3:1: error: unexpected end of input: expect Seperator '}}' \
+                 to close the body of function f opened at 1:13",
+                path
+            )
+        );
+
+        let e = run_args(&["/no/such/file.ps"]).unwrap_err();
+        assert!(e.starts_with("Cannot read /no/such/file.ps: "), "{}", e);
+    }
 
-            // next should be Seperator, ',' or ')'
-            t = self.tokenizer.next().ok_or("invalid token".to_string())?;
-            if t.kind != TokenKind::Seperator || (t.text != "," && t.text != ")") {
-                return Err(format!("expect Seperator ',' or ')' but got {:?}", t).into());
-            }
-            if t.text == "," {
-                // simple skip
-                t = self.tokenizer.next().ok_or("invalid token".to_string())?;
-            }
-        }
-        // 末尾分号
-        let t = self.tokenizer.next().ok_or("invalid token".to_string())?;
-        if t.kind != TokenKind::Seperator || t.text != ";" {
-            return Err(format!("expect Seperator ';' but got {:?}", t).into());
-        }
+    #[test]
+    fn runs_program_read_from_stdin() {
+        let code = read_all(&b"function sayHello(){ println(\"hi\"); }\nsayHello();"[..]).unwrap();
+        assert_eq!(
+            compile_and_run(&code, "<stdin>", Interpreter::new()),
+            Ok(())
+        );
+
+        let code = read_all(&b"sayHello();"[..]).unwrap();
+        assert_eq!(
+            compile_and_run(&code, "<stdin>", Interpreter::new()),
+            Err("unkown function sayHello at 1:1".to_string())
+        );
+
+        let e = read_all(&[0xff, 0xfe][..]).unwrap_err();
+        assert!(e.starts_with("Cannot read stdin: "), "{}", e);
+    }
 
-        // 解析成功
-        return Ok(FunctionCall::new(function_name, function_parameters));
+    #[test]
+    fn repl_keeps_state_between_lines() {
+        let mut repl = Repl::new();
+        assert_eq!(
+            repl.eval("function double(x){ return x * 2; }"),
+            Ok(Value::Null)
+        );
+        assert_eq!(repl.eval("let y = double(3);"), Ok(Value::Null));
+        assert_eq!(repl.eval("y + 1;"), Ok(Value::Int(7)));
+
+        // 出错的输入被丢弃，之后的输入不受影响
+        assert!(repl.eval("let = ;").is_err());
+        assert_eq!(
+            repl.eval("missing();"),
+            Err("unkown function missing at 1:1".to_string())
+        );
+        assert_eq!(repl.eval("double(y);"), Ok(Value::Int(12)));
     }
-}
 
-/////////////////////////////////////////////////////////////////////////
-// 语义分析
-use l01::{Interpreter, RefResolver};
+    #[test]
+    fn check_fails_on_undefined_call() {
+        let code = "function f(){}\nf();\nundefined();";
+        let path = temp_file("check.ps", code);
+        // 返回 Err 时 main 以退出码 1 结束
+        assert_eq!(
+            run_args(&["--check", path.to_str().unwrap()]),
+            Err("1 error(s) found".to_string())
+        );
+        let diagnostics = diagnose(code, "check.ps", &mut load_module).unwrap();
+        let errors: Vec<String> = diagnostics.errors.iter().map(|x| x.to_string()).collect();
+        assert_eq!(errors, vec!["unkown function undefined at 3:1"]);
+
+        let path = temp_file("check_ok.ps", "function f(){}\nf();");
+        assert_eq!(run_args(&["--check", path.to_str().unwrap()]), Ok(()));
+    }
 
-/////////////////////////////////////////////////////////////////////////
-// 主程序
-fn compile_and_run(code: &str) {
-    // 词法分析（模拟）
-    let tokenizer = Tokenizer::new(code);
-    {
-        let tokenizer = Tokenizer::new(dbg!(code));
-        println!("\nAST:");
-        for token in tokenizer {
-            println!("{:?}", token);
+    #[test]
+    fn empty_input_is_an_empty_program() {
+        for code in &["", "   ", "// just a comment"] {
+            assert_eq!(parse(code).unwrap().stmts.len(), 0, "{:?}", code);
+            assert_eq!(
+                compile_and_run(code, "<stdin>", Interpreter::new()),
+                Ok(()),
+                "{:?}",
+                code
+            );
         }
     }
 
-    // 语法分析
-    let mut prog = Parser::new(tokenizer).parse_prog().unwrap();
-    println!("\n语法分析后的AST:");
-    prog.dump("");
-
-    // 语义分析
-    RefResolver::resolve(&mut prog).unwrap();
-    println!("\n语义分析后的AST:");
-    prog.dump("");
-
-    // 运行程序
-    println!("\n运行程序");
-    Interpreter::run(&prog).unwrap();
-}
-
-const DEFAULT_CODE: &str = include_str!("default.ps");
+    #[test]
+    fn emitted_tokens_are_aligned() {
+        let tokens = tokenize("function sayHello(){}\nsayHello();").unwrap();
+        let text = format_tokens(&tokens);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "1:1   Keyword     \"function\"");
+        assert_eq!(lines[7], "2:9   Seperator   \"(\"");
+        assert_eq!(lines.len(), tokens.len());
+
+        let path = temp_file("tokens.ps", "sayHello();");
+        assert_eq!(run_args(&["--emit-tokens", path.to_str().unwrap()]), Ok(()));
+    }
 
-fn main() {
-    compile_and_run(DEFAULT_CODE)
+    #[test]
+    fn plain_error_has_aligned_caret() {
+        let code = "sayHello();\n  undefined();\n";
+        assert_eq!(
+            render_error("unkown function undefined at 2:3", code, "main.ps", false),
+            "main.ps:2:3: error: unkown function undefined\n  undefined();\n  ^"
+        );
+        // 制表符缩进时 ^ 前也使用制表符
+        assert_eq!(
+            render_error("oops at 1:2", "\tf();", "main.ps", false),
+            "main.ps:1:2: error: oops\n\tf();\n\t^"
+        );
+        // 没有位置时只有文件名与错误信息
+        assert_eq!(
+            render_error("Division by zero", code, "main.ps", false),
+            "main.ps: error: Division by zero"
+        );
+        assert!(render_error("oops at 1:1", "f();", "main.ps", true).contains("\x1b[1;31m^\x1b[0m"));
+    }
 }